@@ -1,3 +1,6 @@
+// casts of `InRangeIndex` are no-ops unless the feature `index-u32` is enabled
+#![allow(clippy::unnecessary_cast)]
+
 use super::numeric::FloatingPointParts;
 use super::sorted_sequence::SortedSequence;
 use rand::prelude::{Distribution, SliceRandom};
@@ -12,6 +15,8 @@ pub struct DynamicWeightedIndex {
 impl DynamicWeightedIndex {
     /// Constructs a [`DynamicWeightedIndex`] on `n` elements; initially all elements have weight 0.
     pub fn new(n: usize) -> Self {
+        assert!(n <= InRangeIndex::MAX as usize);
+
         Self {
             indices: vec![None; n],
             levels: vec![Default::default(); 5],
@@ -56,7 +61,7 @@ impl DynamicWeightedIndex {
     /// Returns the weight of the `index`-th element (`None` if the element is uninitialized)
     pub fn weight(&self, idx: usize) -> f64 {
         self.indices[idx].map_or(0.0, |idx| {
-            self.get_range(0, idx.range_index).elements[idx.index_in_range].weight
+            self.get_range(0, idx.range_index).elements[idx.index_in_range as usize].weight
         })
     }
 
@@ -99,10 +104,16 @@ impl DynamicWeightedIndex {
 
 const MAX_NUM_RANGES: usize = (f64::MAX_EXP - f64::MIN_EXP + 1) as usize;
 
+/// With the feature `index-u32`, an `Option<RangeIndex>` takes 12 instead of 24 bytes
+#[cfg(not(feature = "index-u32"))]
+type InRangeIndex = usize;
+#[cfg(feature = "index-u32")]
+type InRangeIndex = u32;
+
 #[derive(Default, Debug, Eq, PartialEq, Clone, Copy)]
 struct RangeIndex {
     range_index: u32,
-    index_in_range: usize,
+    index_in_range: InRangeIndex,
 }
 
 #[derive(Debug, Clone)]
@@ -194,7 +205,7 @@ impl DynamicWeightedIndex {
         level_index: u32,
         range_index: u32,
         new_element: IndexAndWeight,
-    ) -> usize {
+    ) -> InRangeIndex {
         let range = self.get_range_mut(level_index, range_index);
         let old_num_elements = range.elements.len();
 
@@ -202,7 +213,7 @@ impl DynamicWeightedIndex {
         let (old_weight, new_weight) = range.increase_weight_by(new_element.weight);
 
         // push element
-        let elements_new_index = range.elements.len();
+        let elements_new_index = range.elements.len() as InRangeIndex;
         range.elements.push(new_element);
 
         // recursive update; since we added an element, we know that ..
@@ -237,16 +248,19 @@ impl DynamicWeightedIndex {
         let old_num_elements = range.elements.len();
 
         // update weight
-        let weight_removed = range.elements[range_index.index_in_range].weight;
+        let weight_removed = range.elements[range_index.index_in_range as usize].weight;
         let (old_weight, _new_weight) = range.increase_weight_by(-weight_removed);
 
         // remove entry from elements
-        let last_element_is_removed = range_index.index_in_range + 1 == range.elements.len();
+        let last_element_is_removed =
+            range_index.index_in_range as usize + 1 == range.elements.len();
         if last_element_is_removed {
             range.elements.pop();
         } else {
             let element_moved_to_front = range.elements.last().unwrap().index;
-            range.elements.swap_remove(range_index.index_in_range);
+            range
+                .elements
+                .swap_remove(range_index.index_in_range as usize);
 
             if level_index == 0 {
                 self.indices[element_moved_to_front] = Some(range_index);
@@ -318,7 +332,7 @@ impl DynamicWeightedIndex {
 
                 let (old_parent_weight, _) =
                     parent_range.increase_weight_by(new_weight - old_weight);
-                parent_range.elements[parent.index_in_range].weight = new_weight;
+                parent_range.elements[parent.index_in_range as usize].weight = new_weight;
 
                 self.update_range_weight(level + 1, parent.range_index, old_parent_weight);
             } else {
@@ -333,7 +347,7 @@ impl DynamicWeightedIndex {
 
         // update element's weight
         let old_weight = parent.elements[parent_range_index.index_in_range as usize].weight;
-        parent.elements[parent_range_index.index_in_range].weight = new_weight;
+        parent.elements[parent_range_index.index_in_range as usize].weight = new_weight;
 
         // update parent's total weight
         let weight_increase = new_weight - old_weight;
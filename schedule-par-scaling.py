@@ -23,6 +23,8 @@ for (seed, (node, scaling), deg, expon, algo, num_threads) in itertools.product(
 
     if algo == "dyn" or algo == "polypa" or algo == "polypa-prefetch" or algo == "par-polypa":
         cmd = f"target/release/rust-nlpa -a {algo} -s {seed} -n {node} -d {deg} -e {expon} -t {num_threads} -p"
+        if algo == "par-polypa":
+            cmd += " --pin-threads compact"
     else:
         assert(False)
 
@@ -0,0 +1,226 @@
+//! Pinning of the threads of the parallel algorithms to CPUs (Linux only, via
+//! `sched_setaffinity`). The placement policies use the topology reported in
+//! `/sys/devices/system/cpu/cpu*/topology` and only consider the CPUs the process may run on.
+
+use itertools::Itertools;
+use std::io;
+use std::str::FromStr;
+
+#[derive(Eq, Clone, PartialEq, Debug)]
+pub enum ThreadPinning {
+    /// Consecutive ranks share a socket and, if available, the hyperthreads of a core
+    Compact,
+    /// Consecutive ranks alternate between sockets and first occupy distinct cores
+    Scatter,
+    /// Rank `i` runs on the `i`-th CPU of the list (modulo its length)
+    List(Vec<usize>),
+}
+
+impl FromStr for ThreadPinning {
+    type Err = String;
+
+    /// Accepts `compact`, `scatter`, or a non-empty comma-separated list of CPUs and increasing
+    /// ranges (e.g. `0-3,8`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "compact" => return Ok(ThreadPinning::Compact),
+            "scatter" => return Ok(ThreadPinning::Scatter),
+            _ => {}
+        }
+
+        let parse = |x: &str| {
+            x.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Unknown thread pinning: {}", s))
+        };
+
+        let mut cpus = Vec::new();
+        for part in s.split(',') {
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (parse(first)?, parse(last)?);
+                    if first > last {
+                        return Err(format!("Reversed CPU range: {}", part));
+                    }
+                    cpus.extend(first..=last);
+                }
+                None => cpus.push(parse(part)?),
+            }
+        }
+
+        Ok(ThreadPinning::List(cpus))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Cpu {
+    id: usize,
+    package: usize,
+    core: usize,
+    /// Position among the hyperthreads of its core
+    sibling: usize,
+}
+
+impl ThreadPinning {
+    /// Returns the CPU of each of the `num_threads` ranks; fails if a listed CPU is not available
+    /// to the process (rather than letting a thread fail to pin itself while the others wait for
+    /// it at a barrier)
+    pub fn cpus(&self, num_threads: usize) -> io::Result<Vec<usize>> {
+        let list = match self {
+            ThreadPinning::List(list) => {
+                let allowed = allowed_cpus()?;
+                if let Some(cpu) = list.iter().find(|cpu| !allowed.contains(cpu)) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("CPU {} is not available; allowed are {:?}", cpu, allowed),
+                    ));
+                }
+                list.clone()
+            }
+            ThreadPinning::Compact => {
+                let mut cpus = topology()?;
+                cpus.sort_by_key(|c| (c.package, c.core, c.sibling));
+                cpus.iter().map(|c| c.id).collect()
+            }
+            ThreadPinning::Scatter => {
+                let mut cpus = topology()?;
+                cpus.sort_by_key(|c| (c.sibling, c.core));
+
+                // round robin over the packages, each in the order above
+                let packages = cpus
+                    .iter()
+                    .into_group_map_by(|c| c.package)
+                    .into_iter()
+                    .sorted_by_key(|(p, _)| *p)
+                    .map(|(_, cpus)| cpus)
+                    .collect_vec();
+                let rounds = packages.iter().map(|p| p.len()).max().unwrap_or(0);
+
+                (0..rounds)
+                    .flat_map(|i| packages.iter().filter_map(move |p| p.get(i)))
+                    .map(|c| c.id)
+                    .collect()
+            }
+        };
+
+        if list.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} selects no CPUs", self),
+            ));
+        }
+
+        Ok((0..num_threads)
+            .map(|rank| list[rank % list.len()])
+            .collect())
+    }
+}
+
+/// Restricts the calling thread to `cpu`
+pub fn pin_current_thread(cpu: usize) -> io::Result<()> {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("CPU {} exceeds the CPU set size", cpu),
+        ));
+    }
+
+    // safety: the set is zero-initialized plain data and `cpu` is within its size
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// CPUs the calling thread may run on, in increasing order
+fn allowed_cpus() -> io::Result<Vec<usize>> {
+    // safety: the set is zero-initialized plain data, which the call overwrites
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+            .collect_vec())
+    }
+}
+
+/// CPUs the process may run on, with their topology; missing topology files put every CPU on a
+/// core of its own in package zero
+fn topology() -> io::Result<Vec<Cpu>> {
+    let allowed = allowed_cpus()?;
+
+    let read = |cpu: usize, file: &str| {
+        std::fs::read_to_string(format!(
+            "/sys/devices/system/cpu/cpu{}/topology/{}",
+            cpu, file
+        ))
+        .ok()
+        .and_then(|s| s.trim().parse::<usize>().ok())
+    };
+
+    let mut cpus = allowed
+        .into_iter()
+        .map(|id| Cpu {
+            id,
+            package: read(id, "physical_package_id").unwrap_or(0),
+            core: read(id, "core_id").unwrap_or(id),
+            sibling: 0,
+        })
+        .collect_vec();
+
+    // number the hyperthreads of each core in the order of their ids
+    for (_, group) in &cpus
+        .iter_mut()
+        .sorted_by_key(|c| (c.package, c.core, c.id))
+        .group_by(|c| (c.package, c.core))
+    {
+        for (sibling, cpu) in group.enumerate() {
+            cpu.sibling = sibling;
+        }
+    }
+
+    Ok(cpus)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_cpu_lists() {
+        assert_eq!("compact".parse(), Ok(ThreadPinning::Compact));
+        assert_eq!("Scatter".parse(), Ok(ThreadPinning::Scatter));
+        assert_eq!(
+            "0-3,8".parse(),
+            Ok(ThreadPinning::List(vec![0, 1, 2, 3, 8]))
+        );
+        assert!("".parse::<ThreadPinning>().is_err());
+        assert!("0-x".parse::<ThreadPinning>().is_err());
+        assert!("3-1".parse::<ThreadPinning>().is_err());
+        assert!("0,3-1".parse::<ThreadPinning>().is_err());
+        assert!(",".parse::<ThreadPinning>().is_err());
+    }
+
+    #[test]
+    fn policies_use_allowed_cpus() {
+        for pinning in [ThreadPinning::Compact, ThreadPinning::Scatter] {
+            let cpus = pinning.cpus(3).unwrap();
+            assert_eq!(cpus.len(), 3);
+            pin_current_thread(cpus[0]).unwrap();
+        }
+
+        assert!(ThreadPinning::List(vec![libc::CPU_SETSIZE as usize - 1])
+            .cpus(1)
+            .is_err());
+        assert!(ThreadPinning::List(vec![]).cpus(1).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Rejection sampling of hosts: a proposal `u` is accepted iff a uniform 64-bit variate is below
+//! `excess(u) * wmax_scaled`, where `wmax_scaled = 2^64 / wmax`. The scalar path tests one
+//! proposal at a time; the batched path draws `K` proposals (prefetching their node infos) and
+//! `K` variates, compares them with SIMD instructions, and returns the first accepted proposal.
+//!
+//! Both paths yield the same distribution: the trials are independent, so the first accepted
+//! one is distributed identically whether trials are drawn one by one or in batches whose
+//! remainder is discarded. Only the random stream (and hence the graph for a fixed seed)
+//! differs.
+
+use super::*;
+use std::simd::cmp::SimdPartialOrd;
+use std::simd::num::SimdFloat;
+use std::simd::Simd;
+
+/// Batch length of the algorithms' batched paths
+pub const BATCH_LEN: usize = 4;
+
+/// Result of the rejection sampling of one host
+#[derive(Clone, Copy, Debug)]
+pub struct Accepted {
+    pub host: Node,
+    /// Proposals up to and including the host (excluding the discarded rest of its batch)
+    pub proposals: u64,
+    /// Among those, the proposals not rejected early
+    pub tested: u64,
+}
+
+/// Returns the first proposal that passes the acceptance test. `propose` draws a proposal,
+/// `excess` returns its excess weight, or zero to reject it early (e.g. since it is already a
+/// host).
+#[inline]
+pub fn sample_scalar<R: Rng>(
+    rng: &mut R,
+    wmax_scaled: f64,
+    mut propose: impl FnMut(&mut R) -> Node,
+    mut excess: impl FnMut(Node) -> f64,
+) -> Accepted {
+    let mut proposals = 0;
+    let mut tested = 0;
+    loop {
+        proposals += 1;
+        let proposal = propose(rng);
+
+        let excess = excess(proposal);
+        if excess > 0.0 {
+            tested += 1;
+            if rng.gen::<u64>() < (excess * wmax_scaled) as u64 {
+                break Accepted {
+                    host: proposal,
+                    proposals,
+                    tested,
+                };
+            }
+        }
+    }
+}
+
+/// Like [`sample_scalar`], but draws `K` proposals before reading the first excess, so that
+/// prefetches issued by `propose` overlap
+#[inline]
+pub fn sample_batched<R: Rng, const K: usize>(
+    rng: &mut R,
+    wmax_scaled: f64,
+    mut propose: impl FnMut(&mut R) -> Node,
+    mut excess: impl FnMut(Node) -> f64,
+) -> Accepted {
+    let mut proposals = 0;
+    let mut tested = 0;
+    loop {
+        let batch: [Node; K] = std::array::from_fn(|_| propose(rng));
+        let variates: [u64; K] = std::array::from_fn(|_| rng.gen());
+        let excesses = batch.map(&mut excess);
+
+        let accepted = first_accepted(&variates, &excesses, wmax_scaled);
+        let consumed = accepted.map_or(K, |i| i + 1);
+
+        proposals += consumed as u64;
+        tested += excesses[..consumed].iter().filter(|&&e| e > 0.0).count() as u64;
+
+        if let Some(i) = accepted {
+            break Accepted {
+                host: batch[i],
+                proposals,
+                tested,
+            };
+        }
+    }
+}
+
+/// Index of the first lane whose variate is below its threshold `excess * wmax_scaled`; the
+/// conversion saturates like the scalar `as u64`, and an excess of zero is never accepted
+#[inline]
+pub fn first_accepted<const K: usize>(
+    variates: &[u64; K],
+    excesses: &[f64; K],
+    wmax_scaled: f64,
+) -> Option<usize> {
+    let thresholds: Simd<u64, K> = (Simd::from_array(*excesses) * Simd::splat(wmax_scaled)).cast();
+    let accepted = Simd::from_array(*variates).simd_lt(thresholds);
+
+    accepted.first_set()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pcg_rand::Pcg64;
+    use rand::SeedableRng;
+
+    #[test]
+    fn batched_matches_scalar_thresholds() {
+        let scale = 2.0 * (1u64 << 63) as f64;
+        let excesses = [0.0, 0.5, 1.0, 0.25, 1.0, 0.75, 0.0, 0.125];
+        let mut rng = Pcg64::seed_from_u64(3);
+
+        for _ in 0..10000 {
+            let variates: [u64; 8] = std::array::from_fn(|_| rng.gen());
+            let expected =
+                (0..8).find(|&i| excesses[i] > 0.0 && variates[i] < (excesses[i] * scale) as u64);
+            assert_eq!(first_accepted(&variates, &excesses, scale), expected);
+        }
+
+        // an excess of wmax saturates, so that all but the largest variate are accepted
+        assert_eq!(first_accepted(&[u64::MAX; 4], &[1.0; 4], scale), None);
+        assert_eq!(
+            first_accepted(&[u64::MAX - 1; 4], &[1.0; 4], scale),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn batched_and_scalar_agree_in_distribution() {
+        // node u has excess (u + 1) / 4, so accepted nodes are proportional to u + 1
+        const NUM_SAMPLES: usize = 200_000;
+        let scale = 2.0 * (1u64 << 63) as f64;
+        let excess = |u: Node| (u as f64 + 1.0) / 4.0;
+        let propose = |rng: &mut Pcg64| rng.gen_range(0..4) as Node;
+
+        let mut rng = Pcg64::seed_from_u64(5);
+        let mut counts = [[0usize; 4]; 2];
+        for _ in 0..NUM_SAMPLES {
+            counts[0][sample_scalar(&mut rng, scale, propose, excess).host as usize] += 1;
+            counts[1][sample_batched::<_, 8>(&mut rng, scale, propose, excess).host as usize] += 1;
+        }
+
+        for c in counts {
+            for (u, &count) in c.iter().enumerate() {
+                let p = (u as f64 + 1.0) / 10.0;
+                let expected = p * NUM_SAMPLES as f64;
+                let sd = (NUM_SAMPLES as f64 * p * (1.0 - p)).sqrt();
+                assert!((count as f64 - expected).abs() < 5.0 * sd, "{:?}", counts);
+            }
+        }
+    }
+}
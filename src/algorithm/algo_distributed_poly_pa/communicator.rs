@@ -0,0 +1,546 @@
+use super::*;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Where the processes listen; process `rank` uses `port + rank` (TCP) or `path.rank` (Unix).
+///
+/// # Example
+/// ```
+/// use rust_nlpa::algorithm::algo_distributed_poly_pa::Address;
+/// let tcp: Address = "tcp:127.0.0.1:7340".parse().unwrap();
+/// let unix: Address = "unix:/tmp/nlpa".parse().unwrap();
+/// assert!("udp:127.0.0.1:7340".parse::<Address>().is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub enum Address {
+    Tcp { host: String, base_port: u16 },
+    Unix { prefix: PathBuf },
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(Address::Unix {
+                prefix: PathBuf::from(path),
+            });
+        }
+
+        if let Some(host_and_port) = s.strip_prefix("tcp:") {
+            if let Some((host, port)) = host_and_port.rsplit_once(':') {
+                let base_port = port
+                    .parse()
+                    .map_err(|_| format!("Invalid port in address: {}", s))?;
+
+                return Ok(Address::Tcp {
+                    host: host.to_string(),
+                    base_port,
+                });
+            }
+        }
+
+        Err(format!(
+            "Unknown address: {} (expected tcp:<host>:<port> or unix:<path>)",
+            s
+        ))
+    }
+}
+
+impl Address {
+    fn unix_path(prefix: &Path, rank: usize) -> PathBuf {
+        let mut path = prefix.as_os_str().to_owned();
+        path.push(format!(".{}", rank));
+        path.into()
+    }
+
+    fn tcp_port(base_port: u16, rank: usize) -> io::Result<u16> {
+        u16::try_from(base_port as usize + rank)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "port out of range"))
+    }
+
+    fn listen(&self, rank: usize) -> io::Result<Listener> {
+        match self {
+            Address::Tcp { host, base_port } => Ok(Listener::Tcp(TcpListener::bind((
+                host.as_str(),
+                Self::tcp_port(*base_port, rank)?,
+            ))?)),
+            Address::Unix { prefix } => {
+                let path = Self::unix_path(prefix, rank);
+                let _ = std::fs::remove_file(&path);
+                Ok(Listener::Unix(UnixListener::bind(&path)?, path))
+            }
+        }
+    }
+
+    fn connect(&self, rank: usize) -> io::Result<Stream> {
+        match self {
+            Address::Tcp { host, base_port } => {
+                let stream =
+                    TcpStream::connect((host.as_str(), Self::tcp_port(*base_port, rank)?))?;
+                stream.set_nodelay(true)?;
+                Ok(Stream::Tcp(stream))
+            }
+            Address::Unix { prefix } => Ok(Stream::Unix(UnixStream::connect(Self::unix_path(
+                prefix, rank,
+            ))?)),
+        }
+    }
+
+    /// Peers may not listen yet, so we retry until [`CONNECT_TIMEOUT`] expires
+    fn connect_with_retry(&self, rank: usize) -> io::Result<Stream> {
+        let start = Instant::now();
+        loop {
+            match self.connect(rank) {
+                Ok(stream) => return Ok(stream),
+                Err(e) if start.elapsed() > CONNECT_TIMEOUT => return Err(e),
+                Err(_) => thread::sleep(CONNECT_RETRY_DELAY),
+            }
+        }
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    fn accept(&self) -> io::Result<Stream> {
+        match self {
+            Listener::Tcp(l) => {
+                let stream = l.accept()?.0;
+                stream.set_nodelay(true)?;
+                Ok(Stream::Tcp(stream))
+            }
+            Listener::Unix(l, _) => Ok(Stream::Unix(l.accept()?.0)),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Tcp(s) => Ok(Stream::Tcp(s.try_clone()?)),
+            Stream::Unix(s) => Ok(Stream::Unix(s.try_clone()?)),
+        }
+    }
+
+    fn shutdown_write(&self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.shutdown(Shutdown::Write),
+            Stream::Unix(s) => s.shutdown(Shutdown::Write),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            Stream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            Stream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            Stream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+// WIRE FORMAT /////////////////////////////////////////////////////////////////////////////////////
+
+fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn write_u64_slice(writer: &mut impl Write, values: &[u64]) -> io::Result<()> {
+    write_u64(writer, values.len() as u64)?;
+    for &v in values {
+        write_u64(writer, v)?;
+    }
+    Ok(())
+}
+
+fn read_u64_vec(reader: &mut impl Read) -> io::Result<Vec<u64>> {
+    let len = read_u64(reader)? as usize;
+    (0..len).map(|_| read_u64(reader)).collect()
+}
+
+/// Each pair of processes shares one bidirectional data connection (used for collectives) and
+/// each process has a lookup connection to every other process (served by a background thread).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Channel {
+    Data = 0,
+    Lookup = 1,
+}
+
+// LOOKUPS /////////////////////////////////////////////////////////////////////////////////////////
+
+/// A remote read of the node state; the owner of the requested data answers with a
+/// [`LookupResult`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum Lookup {
+    /// The info of a node owned by the receiver
+    Node(Node),
+    /// The `i`-th entry in the receiver's part of the proposal list (and its node's info)
+    Proposal(usize),
+    /// A node of the receiver drawn with probability proportional to the weight it gained in
+    /// the previous epoch; the argument is a uniform variate in `[0, 2^64)`
+    Gain(u64),
+}
+
+impl Lookup {
+    fn encode(self) -> (u64, u64) {
+        match self {
+            Lookup::Node(u) => (0, u as u64),
+            Lookup::Proposal(i) => (1, i as u64),
+            Lookup::Gain(x) => (2, x),
+        }
+    }
+
+    fn decode(kind: u64, arg: u64) -> io::Result<Self> {
+        match kind {
+            0 => Ok(Lookup::Node(arg as Node)),
+            1 => Ok(Lookup::Proposal(arg as usize)),
+            2 => Ok(Lookup::Gain(arg)),
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "unknown lookup")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(super) struct LookupResult {
+    pub(super) node: Node,
+    pub(super) weight: f64,
+    pub(super) count: Node,
+    /// Weight gained in the previous epoch
+    pub(super) gain: f64,
+}
+
+pub(super) type LookupHandler = Arc<dyn Fn(&[Lookup], &mut Vec<LookupResult>) + Send + Sync>;
+
+fn serve_lookups(mut stream: Stream, handler: LookupHandler) -> io::Result<()> {
+    let mut writer = BufWriter::new(stream.try_clone()?);
+    let mut reader = BufReader::new(&mut stream);
+
+    let mut requests = Vec::new();
+    let mut results = Vec::new();
+
+    loop {
+        let num_requests = match read_u64(&mut reader) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        requests.clear();
+        for _ in 0..num_requests {
+            let kind = read_u64(&mut reader)?;
+            let arg = read_u64(&mut reader)?;
+            requests.push(Lookup::decode(kind, arg)?);
+        }
+
+        results.clear();
+        handler(&requests, &mut results);
+        debug_assert_eq!(results.len(), requests.len());
+
+        for r in &results {
+            write_u64(&mut writer, r.node as u64)?;
+            write_u64(&mut writer, r.weight.to_bits())?;
+            write_u64(&mut writer, r.count as u64)?;
+            write_u64(&mut writer, r.gain.to_bits())?;
+        }
+        writer.flush()?;
+    }
+}
+
+// COMMUNICATOR ////////////////////////////////////////////////////////////////////////////////////
+
+struct Peer {
+    data_reader: BufReader<Stream>,
+    data_writer: BufWriter<Stream>,
+    lookup_reader: BufReader<Stream>,
+    lookup_writer: BufWriter<Stream>,
+}
+
+pub(super) struct Communicator {
+    rank: usize,
+    num_processes: usize,
+    peers: Vec<Option<Peer>>,
+    servers: Vec<JoinHandle<io::Result<()>>>,
+}
+
+impl Communicator {
+    /// Establishes the connections to all other processes. Blocks until all peers are reachable.
+    pub(super) fn connect(
+        address: &Address,
+        rank: usize,
+        num_processes: usize,
+        handler: LookupHandler,
+    ) -> io::Result<Self> {
+        assert!(rank < num_processes);
+
+        let listener = address.listen(rank)?;
+
+        let mut data = (0..num_processes).map(|_| None).collect_vec();
+        let mut lookups = (0..num_processes).map(|_| None).collect_vec();
+
+        // outgoing: lookups to all peers, data to peers with smaller rank
+        for peer in (0..num_processes).filter(|&p| p != rank) {
+            let mut stream = address.connect_with_retry(peer)?;
+            write_u64(&mut stream, rank as u64)?;
+            write_u64(&mut stream, Channel::Lookup as u64)?;
+            lookups[peer] = Some(stream);
+
+            if peer < rank {
+                let mut stream = address.connect_with_retry(peer)?;
+                write_u64(&mut stream, rank as u64)?;
+                write_u64(&mut stream, Channel::Data as u64)?;
+                data[peer] = Some(stream);
+            }
+        }
+
+        // incoming: lookups from all peers, data from peers with larger rank
+        let num_incoming = (num_processes - 1) + (num_processes - rank - 1);
+        let mut servers = Vec::with_capacity(num_processes - 1);
+        for _ in 0..num_incoming {
+            let mut stream = listener.accept()?;
+            let peer = read_u64(&mut stream)? as usize;
+            let channel = read_u64(&mut stream)?;
+
+            if peer >= num_processes || peer == rank {
+                return Err(io::Error::new(ErrorKind::InvalidData, "invalid peer rank"));
+            }
+
+            if channel == Channel::Data as u64 {
+                data[peer] = Some(stream);
+            } else {
+                let handler = handler.clone();
+                servers.push(thread::spawn(move || serve_lookups(stream, handler)));
+            }
+        }
+
+        let peers = data
+            .into_iter()
+            .zip(lookups)
+            .map(|(data, lookup)| -> io::Result<Option<Peer>> {
+                match (data, lookup) {
+                    (Some(data), Some(lookup)) => Ok(Some(Peer {
+                        data_reader: BufReader::new(data.try_clone()?),
+                        data_writer: BufWriter::new(data),
+                        lookup_reader: BufReader::new(lookup.try_clone()?),
+                        lookup_writer: BufWriter::new(lookup),
+                    })),
+                    _ => Ok(None),
+                }
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        debug_assert_eq!(peers.iter().filter(|p| p.is_none()).count(), 1);
+
+        Ok(Self {
+            rank,
+            num_processes,
+            peers,
+            servers,
+        })
+    }
+
+    pub(super) fn rank(&self) -> usize {
+        self.rank
+    }
+
+    pub(super) fn num_processes(&self) -> usize {
+        self.num_processes
+    }
+
+    fn is_root(&self) -> bool {
+        self.rank == 0
+    }
+
+    /// Every process contributes a slice of the same length; all processes receive the
+    /// concatenation of all slices ordered by rank. The implementation gathers at rank 0
+    /// and broadcasts from there; it acts as a barrier.
+    pub(super) fn all_gather(&mut self, values: &[u64]) -> io::Result<Vec<u64>> {
+        if self.is_root() {
+            let mut result = Vec::with_capacity(values.len() * self.num_processes);
+            result.extend_from_slice(values);
+
+            for peer in self.peers.iter_mut().flatten() {
+                let received = read_u64_vec(&mut peer.data_reader)?;
+                if received.len() != values.len() {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "length mismatch"));
+                }
+                result.extend(received);
+            }
+
+            for peer in self.peers.iter_mut().flatten() {
+                write_u64_slice(&mut peer.data_writer, &result)?;
+                peer.data_writer.flush()?;
+            }
+
+            Ok(result)
+        } else {
+            let root = self.peers[0].as_mut().unwrap();
+            write_u64_slice(&mut root.data_writer, values)?;
+            root.data_writer.flush()?;
+            read_u64_vec(&mut root.data_reader)
+        }
+    }
+
+    /// Every process contributes a slice of arbitrary length; rank 0 receives all slices indexed
+    /// by rank, all other processes receive `None`
+    pub(super) fn gather(&mut self, values: &[u64]) -> io::Result<Option<Vec<Vec<u64>>>> {
+        if self.is_root() {
+            let mut result = Vec::with_capacity(self.num_processes);
+            result.push(values.to_vec());
+
+            for peer in self.peers.iter_mut().flatten() {
+                result.push(read_u64_vec(&mut peer.data_reader)?);
+            }
+
+            Ok(Some(result))
+        } else {
+            let root = self.peers[0].as_mut().unwrap();
+            write_u64_slice(&mut root.data_writer, values)?;
+            root.data_writer.flush()?;
+            Ok(None)
+        }
+    }
+
+    /// Returns the sum of `value` over all processes to every process
+    pub(super) fn all_reduce_sum(&mut self, value: u64) -> io::Result<u64> {
+        Ok(self.all_gather(&[value])?.into_iter().sum())
+    }
+
+    /// Sends `messages[p]` to process `p` and returns the messages received from all processes
+    /// (indexed by sender). The own message is passed through without communication.
+    pub(super) fn all_to_all(&mut self, mut messages: Vec<Vec<u64>>) -> io::Result<Vec<Vec<u64>>> {
+        assert_eq!(messages.len(), self.num_processes);
+
+        let mut received = (0..self.num_processes).map(|_| Vec::new()).collect_vec();
+        received[self.rank] = std::mem::take(&mut messages[self.rank]);
+
+        let (writers, readers): (Vec<_>, Vec<_>) = self
+            .peers
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(p, peer)| peer.as_mut().map(|peer| (p, peer)))
+            .map(|(p, peer)| ((p, &mut peer.data_writer), (p, &mut peer.data_reader)))
+            .unzip();
+
+        // sending from a separate thread avoids deadlocks if the socket buffers are full
+        thread::scope(|s| -> io::Result<()> {
+            let messages = &messages;
+            let sender = s.spawn(move || -> io::Result<()> {
+                for (p, writer) in writers {
+                    write_u64_slice(writer, &messages[p])?;
+                    writer.flush()?;
+                }
+                Ok(())
+            });
+
+            for (p, reader) in readers {
+                received[p] = read_u64_vec(reader)?;
+            }
+
+            sender.join().unwrap()
+        })?;
+
+        Ok(received)
+    }
+
+    /// Sends `requests[p]` to process `p` and returns the answers (indexed by process and
+    /// request). The own requests have to be empty; they are answered locally by the caller.
+    pub(super) fn lookup(
+        &mut self,
+        requests: &[Vec<Lookup>],
+    ) -> io::Result<Vec<Vec<LookupResult>>> {
+        assert_eq!(requests.len(), self.num_processes);
+        debug_assert!(requests[self.rank].is_empty());
+
+        // send all requests before reading any answer, so that the peers work concurrently
+        for (peer, requests) in self.peers.iter_mut().zip(requests) {
+            if let Some(peer) = peer.as_mut().filter(|_| !requests.is_empty()) {
+                write_u64(&mut peer.lookup_writer, requests.len() as u64)?;
+                for r in requests {
+                    let (kind, arg) = r.encode();
+                    write_u64(&mut peer.lookup_writer, kind)?;
+                    write_u64(&mut peer.lookup_writer, arg)?;
+                }
+                peer.lookup_writer.flush()?;
+            }
+        }
+
+        self.peers
+            .iter_mut()
+            .zip(requests)
+            .map(|(peer, requests)| match peer.as_mut() {
+                Some(peer) => (0..requests.len())
+                    .map(|_| {
+                        Ok(LookupResult {
+                            node: read_u64(&mut peer.lookup_reader)? as Node,
+                            weight: f64::from_bits(read_u64(&mut peer.lookup_reader)?),
+                            count: read_u64(&mut peer.lookup_reader)? as Node,
+                            gain: f64::from_bits(read_u64(&mut peer.lookup_reader)?),
+                        })
+                    })
+                    .collect(),
+                None => Ok(Vec::new()),
+            })
+            .collect()
+    }
+
+    /// Closes all connections and waits until all peers closed theirs
+    pub(super) fn finish(mut self) -> io::Result<()> {
+        for peer in self.peers.iter_mut().flatten() {
+            peer.lookup_writer.flush()?;
+            peer.lookup_writer.get_ref().shutdown_write()?;
+        }
+
+        for server in self.servers.drain(..) {
+            server.join().unwrap()?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,741 @@
+//! Distributed variant of [`AlgoParallelPolyPa`](super::algo_parallel_poly_pa::AlgoParallelPolyPa)
+//! in which several processes communicate via TCP or Unix sockets.
+//!
+//! Node `u` is owned by process `u % num_processes`, which stores its info and all its
+//! copies in the proposal list. Hence, the global proposal list is the concatenation of the
+//! processes' local lists, and every process knows the lengths of all local lists (they are
+//! exchanged at epoch boundaries). An epoch proceeds as follows:
+//!  1. Each process decides the run length for the new nodes it owns (using the
+//!     [`RunlengthSampler`]); the epoch ends at the minimum over all processes.
+//!  2. Each process samples the hosts of its new nodes. Proposals are resolved by batched
+//!     lookups at their owners; all lookups of an epoch complete before any process reaches the
+//!     next collective operation.
+//!  3. Degree increases are sent to the owners, which update weights and proposal lists.
+//!     Total weight, maximum degree and `wmax` are all-reduced.
+
+#![allow(clippy::too_many_arguments)]
+
+mod communicator;
+
+use super::*;
+use crate::algorithm::algo_parallel_poly_pa::dependent_node::{DependentNode, Gains};
+use crate::algorithm::algo_parallel_poly_pa::reports::Reporter;
+use crate::algorithm::algo_parallel_poly_pa::run_length::RunlengthSampler;
+use crate::edge_writer::degree_distribution;
+use communicator::{Communicator, Lookup, LookupHandler, LookupResult};
+use itertools::Itertools;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Arc, RwLock};
+
+pub use communicator::Address;
+
+const SCALE: f64 = 2.0 * (1u64 << 63) as f64;
+
+#[derive(Clone, Copy, Debug)]
+struct NodeInfo {
+    degree: Node,
+    count: Node,
+    weight: f64,
+}
+
+impl Default for NodeInfo {
+    fn default() -> Self {
+        Self {
+            degree: 0,
+            count: 1,
+            weight: 0.0,
+        }
+    }
+}
+
+/// The part of the graph owned by a process; shared with the threads serving lookups
+struct LocalState {
+    rank: usize,
+    num_processes: usize,
+    nodes: Vec<NodeInfo>,
+    proposal_list: Vec<Node>,
+    gains: Gains,
+}
+
+impl LocalState {
+    fn is_owner(&self, node: Node) -> bool {
+        node as usize % self.num_processes == self.rank
+    }
+
+    fn info(&self, node: Node) -> &NodeInfo {
+        debug_assert!(self.is_owner(node));
+        &self.nodes[node as usize / self.num_processes]
+    }
+
+    fn info_mut(&mut self, node: Node) -> &mut NodeInfo {
+        debug_assert!(self.is_owner(node));
+        &mut self.nodes[node as usize / self.num_processes]
+    }
+
+    fn lookup(&self, request: Lookup) -> LookupResult {
+        let node = match request {
+            Lookup::Node(u) => u,
+            Lookup::Proposal(i) => self.proposal_list[i],
+            Lookup::Gain(x) => self.gains.node_at(x as f64 / SCALE * self.gains.total()),
+        };
+
+        let info = self.info(node);
+        LookupResult {
+            node,
+            weight: info.weight,
+            count: info.count,
+            gain: self.gains.gain(node),
+        }
+    }
+
+    /// Raises the count of a node to its target and returns its new excess
+    fn update_node_counts_in_proposal_list(
+        &mut self,
+        node: Node,
+        assumed_num_nodes: f64,
+        total_weight: f64,
+    ) -> f64 {
+        let info = self.info_mut(node);
+        let target_count = (assumed_num_nodes * info.weight / total_weight).ceil() as Node;
+
+        let num_new = target_count.saturating_sub(info.count);
+        info.count += num_new;
+        let excess = info.weight / info.count as f64;
+
+        self.proposal_list
+            .extend(std::iter::repeat_n(node, num_new as usize));
+
+        excess
+    }
+}
+
+pub struct AlgoDistributedPolyPa<R: Rng> {
+    rng: R,
+    comm: Communicator,
+    state: Arc<RwLock<LocalState>>,
+
+    num_seed_nodes: Node,
+    num_total_nodes: Node,
+    initial_degree: Node,
+    without_replacement: bool,
+    weight_function: WeightFunction,
+
+    runlength_sampler: RunlengthSampler,
+
+    /// Prefix sums over the lengths of the processes' proposal lists at the begin of the epoch
+    proposal_offsets: Vec<usize>,
+    /// Prefix sums over the weights the processes' nodes gained in the previous epoch
+    gain_offsets: Vec<f64>,
+    /// The node ending the previous epoch, unless it ended at the last node
+    dependent: Option<DependentNode>,
+    total_weight: f64,
+    max_degree: Node,
+    wmax: f64,
+
+    reporter: Option<Reporter>,
+}
+
+impl<R: Rng> AlgoDistributedPolyPa<R> {
+    /// Connects to all other processes; hence all processes have to call this function
+    /// concurrently with identical parameters (except for `rng` and `rank`).
+    pub fn new(
+        rng: R,
+        address: &Address,
+        rank: usize,
+        num_processes: usize,
+        num_seed_nodes: Node,
+        num_rand_nodes: Node,
+        initial_degree: Node,
+        without_replacement: bool,
+        weight_function: WeightFunction,
+    ) -> io::Result<Self> {
+        let num_total_nodes = num_seed_nodes + num_rand_nodes;
+        let num_local_nodes = (num_total_nodes as usize + num_processes - 1 - rank) / num_processes;
+
+        let state = Arc::new(RwLock::new(LocalState {
+            rank,
+            num_processes,
+            nodes: vec![Default::default(); num_local_nodes],
+            proposal_list: Vec::with_capacity(4 * num_local_nodes / 3),
+            gains: Gains::default(),
+        }));
+
+        let handler: LookupHandler = {
+            let state = state.clone();
+            Arc::new(move |requests, results| {
+                let state = state.read().unwrap();
+                results.extend(requests.iter().map(|&r| state.lookup(r)));
+            })
+        };
+
+        let runlength_sampler = RunlengthSampler::new(weight_function.clone(), initial_degree)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let comm = Communicator::connect(address, rank, num_processes, handler)?;
+
+        Ok(Self {
+            rng,
+            comm,
+            state,
+
+            num_seed_nodes,
+            num_total_nodes,
+            initial_degree,
+            without_replacement,
+            weight_function,
+
+            runlength_sampler,
+
+            proposal_offsets: vec![0; num_processes + 1],
+            gain_offsets: vec![0.0; num_processes + 1],
+            dependent: None,
+            total_weight: 0.0,
+            max_degree: 0,
+            wmax: 0.0,
+
+            reporter: (rank == 0).then(|| Reporter::new(num_total_nodes)),
+        })
+    }
+
+    /// All processes have to pass the same degree sequence
+    pub fn set_seed_graph_degrees(
+        &mut self,
+        degrees: impl Iterator<Item = Node>,
+    ) -> io::Result<()> {
+        let mut state = self.state.write().unwrap();
+
+        let mut num_input_degrees = 0;
+        let mut min_degree = Node::MAX;
+        for (node, degree) in degrees.enumerate() {
+            let node = node as Node;
+            let weight = self.weight_function.get(degree);
+            self.total_weight += weight;
+            self.max_degree = self.max_degree.max(degree);
+            min_degree = min_degree.min(degree);
+
+            if state.is_owner(node) {
+                let info = state.info_mut(node);
+                info.degree = degree;
+                info.weight = weight;
+            }
+
+            num_input_degrees += 1;
+        }
+
+        assert_eq!(num_input_degrees, self.num_seed_nodes);
+        self.runlength_sampler.set_min_seed_degree(min_degree);
+
+        let mut wmax: f64 = 0.0;
+        let first_node = self.comm.rank() as Node;
+        for node in (first_node..self.num_seed_nodes).step_by(self.comm.num_processes()) {
+            wmax = wmax.max(state.update_node_counts_in_proposal_list(
+                node,
+                self.num_seed_nodes as f64,
+                self.total_weight,
+            ));
+        }
+
+        let num_proposals = state.proposal_list.len();
+        drop(state);
+
+        let gathered = self
+            .comm
+            .all_gather(&[wmax.to_bits(), num_proposals as u64])?;
+        self.finish_epoch(self.num_seed_nodes, &gathered);
+
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut new_nodes = Vec::new();
+        let mut epoch_begin = self.num_seed_nodes;
+        let mut epoch_id = 0;
+
+        while epoch_begin < self.num_total_nodes {
+            epoch_id += 1;
+            let epoch_end = self.sample_epoch_end(epoch_begin, &mut new_nodes)?;
+
+            let hosts = self.sample_hosts(epoch_begin, new_nodes.len())?;
+            self.update_degrees(epoch_end, &new_nodes, &hosts)?;
+
+            if let Some(reporter) = self.reporter.as_mut() {
+                reporter.update_epoch(epoch_id, epoch_begin..epoch_end);
+                reporter.report_progress_sometimes();
+            }
+
+            epoch_begin = epoch_end;
+        }
+
+        if let Some(reporter) = self.reporter.as_mut() {
+            reporter.report_progress_forced();
+        }
+
+        Ok(())
+    }
+
+    /// Gathers the degrees of all nodes at rank 0 and returns `None` on all other ranks. Rank 0
+    /// has to hold all degrees; for large graphs, prefer [`Self::degree_distribution`].
+    pub fn degrees(&mut self) -> io::Result<Option<Vec<Node>>> {
+        let num_processes = self.comm.num_processes();
+
+        let local = {
+            let state = self.state.read().unwrap();
+            state.nodes.iter().map(|i| i.degree as u64).collect_vec()
+        };
+
+        let gathered = self.comm.gather(&local)?;
+
+        Ok(gathered.map(|gathered| {
+            (0..self.num_total_nodes as usize)
+                .map(|u| gathered[u % num_processes][u / num_processes] as Node)
+                .collect()
+        }))
+    }
+
+    /// Gathers the degree distribution (sorted pairs of degree and number of nodes) at rank 0
+    /// and returns `None` on all other ranks. Only the local distributions are sent.
+    pub fn degree_distribution(&mut self) -> io::Result<Option<Vec<(usize, usize)>>> {
+        let local = {
+            let state = self.state.read().unwrap();
+            degree_distribution(state.nodes.iter().map(|i| i.degree))
+                .into_iter()
+                .flat_map(|(degree, count)| [degree as u64, count as u64])
+                .collect_vec()
+        };
+
+        let gathered = self.comm.gather(&local)?;
+
+        Ok(gathered.map(|gathered| {
+            let mut distribution = BTreeMap::new();
+            for (&degree, &count) in gathered.iter().flat_map(|m| m.iter().tuples()) {
+                *distribution.entry(degree as usize).or_insert(0) += count as usize;
+            }
+            distribution.into_iter().collect()
+        }))
+    }
+
+    /// Returns the sum of all degrees on every rank
+    pub fn degree_sum(&mut self) -> io::Result<u64> {
+        let local = {
+            let state = self.state.read().unwrap();
+            state.nodes.iter().map(|i| i.degree as u64).sum()
+        };
+
+        self.comm.all_reduce_sum(local)
+    }
+
+    /// Closes all connections; blocks until all other processes call this function as well
+    pub fn finish(self) -> io::Result<()> {
+        self.comm.finish()
+    }
+
+    /// Collects the nodes owned by this process that are part of the epoch starting at
+    /// `epoch_begin` and returns the end of the epoch (the first node with a dependency)
+    fn sample_epoch_end(
+        &mut self,
+        epoch_begin: Node,
+        new_nodes: &mut Vec<Node>,
+    ) -> io::Result<Node> {
+        let num_processes = self.comm.num_processes();
+        let first_node = epoch_begin
+            + ((self.comm.rank() + num_processes - epoch_begin as usize % num_processes)
+                % num_processes) as Node;
+
+        new_nodes.clear();
+        for node in (first_node..self.num_total_nodes).step_by(num_processes) {
+            // the first node of an epoch is allowed to depend on the previous ones
+            if node != epoch_begin
+                && !self.runlength_sampler.continue_with_node(
+                    &mut self.rng,
+                    node,
+                    self.initial_degree as usize,
+                )
+            {
+                break;
+            }
+
+            new_nodes.push(node);
+        }
+
+        let local_end = self.runlength_sampler.result().0;
+        let epoch_end = self
+            .comm
+            .all_gather(&[local_end as u64])?
+            .into_iter()
+            .min()
+            .unwrap() as Node;
+
+        self.runlength_sampler.restrict_upper(epoch_end);
+        new_nodes.retain(|&u| u < epoch_end);
+
+        Ok(epoch_end)
+    }
+
+    /// Samples `initial_degree` hosts for each of `num_new_nodes` nodes from the state at the
+    /// begin of the epoch. In each round, every incomplete node draws one proposal; all
+    /// proposals of a round are resolved with one batch of lookups per process.
+    fn sample_hosts(&mut self, epoch_begin: Node, num_new_nodes: usize) -> io::Result<Vec<Node>> {
+        let num_processes = self.comm.num_processes();
+        let rank = self.comm.rank();
+        let degree = self.initial_degree as usize;
+
+        let num_proposals = epoch_begin as usize + self.proposal_offsets[num_processes];
+        let wmax_scaled = SCALE / self.wmax;
+
+        let mut hosts = vec![0; num_new_nodes * degree];
+        let mut num_hosts = vec![0; num_new_nodes];
+
+        let mut pending = (0..num_new_nodes).collect_vec();
+
+        // the node ending the previous epoch comes first if this process owns it
+        if let Some(dependent) = self.dependent.take() {
+            if epoch_begin as usize % num_processes == rank && num_new_nodes > 0 {
+                let mut dependent_hosts = Vec::with_capacity(degree);
+                self.sample_dependent_hosts(&dependent, epoch_begin, &mut dependent_hosts)?;
+
+                hosts[..degree].copy_from_slice(&dependent_hosts);
+                num_hosts[0] = degree;
+                pending.remove(0);
+            }
+        }
+        let mut requests = vec![Vec::new(); num_processes];
+        let mut request_of_pending = Vec::with_capacity(num_new_nodes);
+
+        while !pending.is_empty() {
+            request_of_pending.clear();
+            for _ in 0..pending.len() {
+                let index = self.rng.gen_range(0..num_proposals);
+                let (owner, request) = locate(&self.proposal_offsets, index, epoch_begin);
+                request_of_pending.push((owner, requests[owner].len()));
+                requests[owner].push(request);
+            }
+
+            let local_requests = std::mem::take(&mut requests[rank]);
+            let mut results = self.comm.lookup(&requests)?;
+            {
+                let state = self.state.read().unwrap();
+                results[rank] = local_requests.iter().map(|&r| state.lookup(r)).collect();
+            }
+            requests[rank] = local_requests;
+
+            pending = pending
+                .into_iter()
+                .zip(&request_of_pending)
+                .filter(|&(i, &(owner, index))| {
+                    let proposal = results[owner][index];
+                    let previous_hosts = &hosts[i * degree..i * degree + num_hosts[i]];
+
+                    let accept = !(self.without_replacement
+                        && previous_hosts.contains(&proposal.node))
+                        && self.rng.gen::<u64>()
+                            < (proposal.weight / proposal.count as f64 * wmax_scaled) as u64;
+
+                    if accept {
+                        hosts[i * degree + num_hosts[i]] = proposal.node;
+                        num_hosts[i] += 1;
+                    }
+
+                    num_hosts[i] < degree
+                })
+                .map(|(i, _)| i)
+                .collect();
+
+            requests.iter_mut().for_each(|r| r.clear());
+        }
+
+        Ok(hosts)
+    }
+
+    /// Samples the hosts of the node ending the previous epoch (see [`DependentNode`]) with one
+    /// lookup per proposal
+    fn sample_dependent_hosts(
+        &mut self,
+        dependent: &DependentNode,
+        epoch_begin: Node,
+        hosts: &mut Vec<Node>,
+    ) -> io::Result<()> {
+        let num_processes = self.comm.num_processes();
+        let num_proposals = epoch_begin as usize + self.proposal_offsets[num_processes];
+        let wmax_scaled = SCALE / self.wmax;
+        let total_gain = self.gain_offsets[num_processes];
+
+        let comm = RefCell::new(&mut self.comm);
+        let state = &self.state;
+        let proposal_offsets = &self.proposal_offsets;
+        let gain_offsets = &self.gain_offsets;
+
+        let lookup = |owner: usize, request: Lookup| -> io::Result<LookupResult> {
+            let mut comm = comm.borrow_mut();
+            if owner == comm.rank() {
+                return Ok(state.read().unwrap().lookup(request));
+            }
+
+            let mut requests = vec![Vec::new(); num_processes];
+            requests[owner].push(request);
+            Ok(comm.lookup(&requests)?[owner][0])
+        };
+
+        // the result of the last accepted proposal, which `old_fraction` is asked about
+        let last_accepted = Cell::new(LookupResult::default());
+
+        dependent.sample_hosts(
+            &mut self.rng,
+            self.initial_degree as usize,
+            self.without_replacement,
+            hosts,
+            |rng| loop {
+                let index = rng.gen_range(0..num_proposals);
+                let (owner, request) = locate(proposal_offsets, index, epoch_begin);
+                let proposal = lookup(owner, request)?;
+
+                if rng.gen::<u64>() < (proposal.weight / proposal.count as f64 * wmax_scaled) as u64
+                {
+                    last_accepted.set(proposal);
+                    break Ok(proposal.node);
+                }
+            },
+            |rng| {
+                let target = rng.gen::<f64>() * total_gain;
+                let owner = gain_offsets.partition_point(|&o| o <= target) - 1;
+                Ok(lookup(owner.min(num_processes - 1), Lookup::Gain(rng.gen()))?.node)
+            },
+            |node| {
+                let proposal = last_accepted.get();
+                debug_assert_eq!(proposal.node, node);
+                Ok(1.0 - proposal.gain / proposal.weight)
+            },
+        )
+    }
+
+    fn update_degrees(
+        &mut self,
+        epoch_end: Node,
+        new_nodes: &[Node],
+        hosts: &[Node],
+    ) -> io::Result<()> {
+        let num_processes = self.comm.num_processes();
+
+        let mut messages = vec![Vec::new(); num_processes];
+        for (host, degree_increase) in hosts.iter().copied().counts() {
+            messages[host as usize % num_processes].extend([host as u64, degree_increase as u64]);
+        }
+
+        let received = self.comm.all_to_all(messages)?;
+        let degree_increases = received
+            .iter()
+            .flat_map(|m| m.iter().tuples().map(|(&u, &inc)| (u as Node, inc as Node)))
+            .chain(new_nodes.iter().map(|&u| (u, self.initial_degree)));
+
+        let mut state = self.state.write().unwrap();
+
+        let mut gains = Vec::with_capacity(new_nodes.len() + hosts.len());
+        let mut weight_increase = 0.0;
+        let mut max_degree = self.max_degree;
+        for (node, degree_increase) in degree_increases {
+            let info = state.info_mut(node);
+            info.degree += degree_increase;
+            max_degree = max_degree.max(info.degree);
+
+            let new_weight = self.weight_function.get(info.degree);
+            weight_increase += new_weight - info.weight;
+            gains.push((node, new_weight - info.weight));
+            info.weight = new_weight;
+        }
+
+        let updated_nodes = gains.iter().map(|g| g.0).collect_vec();
+        state.gains.set(&mut gains);
+        drop(state);
+
+        // the bound for the node ending this epoch, as used by the run length sampler
+        let weight_before = self.total_weight;
+        let weight_bound = self
+            .runlength_sampler
+            .total_weight_and_upper_bound_for(epoch_end)
+            .1;
+
+        // summing in the order of ranks yields bit-identical total weights on all processes
+        let gathered = self
+            .comm
+            .all_gather(&[weight_increase.to_bits(), max_degree as u64])?;
+        for (i, (increase, degree)) in gathered.into_iter().tuples().enumerate() {
+            self.total_weight += f64::from_bits(increase);
+            self.max_degree = self.max_degree.max(degree as Node);
+            self.gain_offsets[i + 1] = self.gain_offsets[i] + f64::from_bits(increase);
+        }
+
+        self.dependent = (epoch_end < self.num_total_nodes)
+            .then(|| DependentNode::new(weight_before, weight_bound, self.total_weight));
+
+        let mut state = self.state.write().unwrap();
+
+        let mut wmax = self.wmax;
+        for node in updated_nodes {
+            wmax = wmax.max(state.update_node_counts_in_proposal_list(
+                node,
+                epoch_end as f64,
+                self.total_weight,
+            ));
+        }
+
+        let num_proposals = state.proposal_list.len();
+        drop(state);
+
+        let gathered = self
+            .comm
+            .all_gather(&[wmax.to_bits(), num_proposals as u64])?;
+        self.finish_epoch(epoch_end, &gathered);
+
+        Ok(())
+    }
+
+    /// Takes the gathered `(wmax, proposal list length)` of all processes and prepares the epoch
+    /// starting at `epoch_begin`
+    fn finish_epoch(&mut self, epoch_begin: Node, gathered: &[u64]) {
+        for (i, (wmax, num_proposals)) in gathered.iter().tuples().enumerate() {
+            self.wmax = self.wmax.max(f64::from_bits(*wmax));
+            self.proposal_offsets[i + 1] = self.proposal_offsets[i] + *num_proposals as usize;
+        }
+
+        self.runlength_sampler.setup_epoch(
+            epoch_begin,
+            self.num_total_nodes,
+            self.max_degree,
+            self.total_weight,
+        );
+    }
+}
+
+/// Maps an index into the global proposal list to the owner and its local request; the first
+/// `epoch_begin` indices stand for one entry per node
+fn locate(proposal_offsets: &[usize], index: usize, epoch_begin: Node) -> (usize, Lookup) {
+    let epoch_begin = epoch_begin as usize;
+    let num_processes = proposal_offsets.len() - 1;
+    if index < epoch_begin {
+        return (index % num_processes, Lookup::Node(index as Node));
+    }
+
+    let index = index - epoch_begin;
+    let owner = proposal_offsets.partition_point(|&o| o <= index) - 1;
+    (owner, Lookup::Proposal(index - proposal_offsets[owner]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::test_util::Setup;
+    use pcg_rand::Pcg64;
+    use rand::SeedableRng;
+    use std::thread;
+
+    const NUM_SEED_NODES: Node = 20;
+    const INITIAL_DEGREE: Node = 2;
+
+    /// Runs the processes as threads and returns the degrees gathered at rank 0
+    fn run_processes(
+        address: Address,
+        num_processes: usize,
+        num_rand_nodes: Node,
+        seed: u64,
+        exponent: f64,
+    ) -> Vec<Node> {
+        let handles = (0..num_processes)
+            .map(|rank| {
+                let address = address.clone();
+                thread::spawn(move || -> io::Result<_> {
+                    let mut algo = AlgoDistributedPolyPa::new(
+                        Pcg64::seed_from_u64(seed * num_processes as u64 + rank as u64),
+                        &address,
+                        rank,
+                        num_processes,
+                        NUM_SEED_NODES,
+                        num_rand_nodes,
+                        INITIAL_DEGREE,
+                        true,
+                        WeightFunction::new(exponent, 1.0),
+                    )?;
+
+                    algo.set_seed_graph_degrees((0..NUM_SEED_NODES).map(|_| 1))?;
+                    algo.run()?;
+                    let degrees = algo.degrees()?;
+                    let distribution = algo.degree_distribution()?;
+                    let degree_sum = algo.degree_sum()?;
+                    algo.finish()?;
+
+                    Ok((degrees, distribution, degree_sum))
+                })
+            })
+            .collect_vec();
+
+        let mut results = handles
+            .into_iter()
+            .map(|h| h.join().unwrap().unwrap())
+            .collect_vec();
+
+        let expected_sum = (NUM_SEED_NODES + 2 * num_rand_nodes * INITIAL_DEGREE) as u64;
+        assert!(results.iter().all(|(_, _, sum)| *sum == expected_sum));
+        assert!(results
+            .iter()
+            .skip(1)
+            .all(|(degrees, distribution, _)| degrees.is_none() && distribution.is_none()));
+
+        let (degrees, distribution, _) = results.swap_remove(0);
+        let degrees = degrees.unwrap();
+        assert_eq!(degrees.len(), (NUM_SEED_NODES + num_rand_nodes) as usize);
+        assert_eq!(
+            distribution.unwrap(),
+            degree_distribution(degrees.iter().copied())
+        );
+
+        degrees
+    }
+
+    fn unix_address(name: &str) -> Address {
+        let mut prefix = std::env::temp_dir();
+        prefix.push(format!("nlpa-test-{}-{}", name, std::process::id()));
+        Address::Unix { prefix }
+    }
+
+    #[test]
+    fn single_process() {
+        let degrees = run_processes(unix_address("single"), 1, 1000, 1234, 0.5);
+        assert!(degrees[NUM_SEED_NODES as usize..]
+            .iter()
+            .all(|&d| d >= INITIAL_DEGREE));
+    }
+
+    #[test]
+    fn multiple_processes() {
+        for num_processes in [2, 3, 5] {
+            let degrees = run_processes(
+                unix_address(&format!("multiple{}", num_processes)),
+                num_processes,
+                3000,
+                1234,
+                0.5,
+            );
+            assert!(degrees[NUM_SEED_NODES as usize..]
+                .iter()
+                .all(|&d| d >= INITIAL_DEGREE));
+        }
+    }
+
+    #[test]
+    fn statistics_match_dynamic_weighted_index() {
+        for exponent in [0.5, 1.0] {
+            let setup = Setup {
+                repeats: 150,
+                num_seed_nodes: NUM_SEED_NODES,
+                num_rand_nodes: 2000,
+                initial_degree: INITIAL_DEGREE,
+                exponent,
+            };
+
+            let address = unix_address(&format!("statistics{}", exponent));
+            setup.assert_matches_dynamic_weighted_index(|seed| {
+                run_processes(address.clone(), 3, setup.num_rand_nodes, seed, exponent)
+            });
+        }
+    }
+}
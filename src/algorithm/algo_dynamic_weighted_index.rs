@@ -1,11 +1,20 @@
+use super::checkpoint::{self, CheckpointReader, CheckpointWriter, Checkpointer};
 use super::*;
 
 use rand::distributions::Distribution;
 
+/// Samples the hosts exactly from a [`DynamicWeightedIndex`] over all nodes.
+///
+/// The layout of the index depends on the order of its updates, so checkpoints store only the
+/// degrees and the index is rebuilt from them, both when saving and when resuming. Hence, a run
+/// with checkpoints makes different random choices than one without, but the resumed run
+/// continues exactly as the uninterrupted one.
+///
+/// [`DynamicWeightedIndex`]: ::dynamic_weighted_index::DynamicWeightedIndex
 pub struct AlgoDynamicWeightedIndex<R: Rng> {
     rng: R,
     num_seed_nodes: Node,
-    num_rand_nodes: Node,
+    num_total_nodes: Node,
     initial_degree: Node,
     without_replacement: bool,
     resample: bool,
@@ -14,6 +23,10 @@ pub struct AlgoDynamicWeightedIndex<R: Rng> {
     dyn_index: ::dynamic_weighted_index::DynamicWeightedIndex,
 
     weight_function: WeightFunction,
+
+    /// First node not yet inserted by [`Algorithm::run`]
+    next_node: Node,
+    checkpointer: Option<Checkpointer<Self>>,
 }
 
 impl<R: Rng> Algorithm<R> for AlgoDynamicWeightedIndex<R> {
@@ -31,19 +44,23 @@ impl<R: Rng> Algorithm<R> for AlgoDynamicWeightedIndex<R> {
     ) -> Self {
         assert_eq!(num_threads, 1);
 
+        let num_total_nodes = num_seed_nodes + num_rand_nodes;
         Self {
             rng,
             num_seed_nodes,
-            num_rand_nodes,
+            num_total_nodes,
             initial_degree,
             without_replacement,
             weight_function,
             resample,
 
-            degrees: vec![0; num_seed_nodes + num_rand_nodes],
+            degrees: vec![0; num_total_nodes as usize],
             dyn_index: ::dynamic_weighted_index::DynamicWeightedIndex::new(
-                num_seed_nodes + num_rand_nodes,
+                num_total_nodes as usize,
             ),
+
+            next_node: num_seed_nodes,
+            checkpointer: None,
         }
     }
 
@@ -56,11 +73,11 @@ impl<R: Rng> Algorithm<R> for AlgoDynamicWeightedIndex<R> {
     fn run(&mut self, writer: &mut impl EdgeWriter) {
         let mut hosts = vec![0; self.initial_degree as usize];
 
-        for new_node in self.num_seed_nodes..(self.num_seed_nodes + self.num_rand_nodes) {
+        for new_node in self.next_node..self.num_total_nodes {
             if self.without_replacement && self.resample && self.initial_degree > 1 {
-                for i in 0..self.initial_degree {
+                for i in 0..self.initial_degree as usize {
                     let host = loop {
-                        let host = self.dyn_index.sample(&mut self.rng).unwrap();
+                        let host = self.dyn_index.sample(&mut self.rng).unwrap() as Node;
                         if !hosts[0..i].contains(&host) {
                             break host;
                         }
@@ -70,9 +87,9 @@ impl<R: Rng> Algorithm<R> for AlgoDynamicWeightedIndex<R> {
                 }
             } else {
                 for h in &mut hosts {
-                    *h = self.dyn_index.sample(&mut self.rng).unwrap();
+                    *h = self.dyn_index.sample(&mut self.rng).unwrap() as Node;
                     if self.without_replacement && self.initial_degree > 1 {
-                        self.dyn_index.remove_weight(*h);
+                        self.dyn_index.remove_weight(*h as usize);
                     };
                 }
             }
@@ -84,15 +101,86 @@ impl<R: Rng> Algorithm<R> for AlgoDynamicWeightedIndex<R> {
             }
 
             self.set_degree(new_node, self.initial_degree);
+
+            self.next_node = new_node + 1;
+            if self.checkpointer.as_ref().is_some_and(|checkpointer| {
+                checkpointer.save_if_due(self, self.next_node, self.num_total_nodes)
+            }) {
+                self.rebuild_index();
+            }
         }
     }
 
+    fn enable_checkpoints(&mut self, config: CheckpointConfig)
+    where
+        R: Serialize,
+    {
+        self.checkpointer = Some(Checkpointer::new(config, Self::save_checkpoint));
+    }
+
+    fn resume_from_checkpoint(&mut self, path: &Path) -> io::Result<()>
+    where
+        R: DeserializeOwned,
+    {
+        let mut reader = CheckpointReader::open(path)?;
+        reader.expect_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::expect_parameters(
+            &mut reader,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+        checkpoint::expect_weight_function(&mut reader, &self.weight_function)?;
+        reader.expect_u64(self.resample as u64, "resampling")?;
+
+        self.next_node = reader.read_node()?;
+        let mut degrees = self.degrees.iter_mut();
+        reader.read_nodes(|degree| *degrees.next().unwrap() = degree)?;
+        self.rebuild_index();
+
+        self.rng = reader.read_rng()?;
+        Ok(())
+    }
+
     fn degrees(&self) -> Vec<Node> {
         self.degrees.clone()
     }
 }
 
+const CHECKPOINT_TAG: &str = "dyn";
+
 impl<R: Rng> AlgoDynamicWeightedIndex<R> {
+    /// Writes the fields in the order read by [`Algorithm::resume_from_checkpoint`]
+    fn save_checkpoint(&self, writer: &mut CheckpointWriter) -> io::Result<()>
+    where
+        R: Serialize,
+    {
+        writer.write_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::write_parameters(
+            writer,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+        checkpoint::write_weight_function(writer, &self.weight_function)?;
+        writer.write_u64(self.resample as u64)?;
+
+        writer.write_u64(self.next_node as u64)?;
+        writer.write_nodes(&self.degrees[..self.next_node as usize])?;
+        writer.write_rng(&self.rng)
+    }
+
+    /// Replaces the index by one built from the degrees of the nodes inserted so far
+    fn rebuild_index(&mut self) {
+        self.dyn_index =
+            ::dynamic_weighted_index::DynamicWeightedIndex::new(self.num_total_nodes as usize);
+        for node in 0..self.next_node {
+            self.set_degree(node, self.degrees[node as usize]);
+        }
+    }
+
     fn set_degree(&mut self, node: Node, degree: Node) {
         self.degrees[node as usize] = degree;
         self.dyn_index
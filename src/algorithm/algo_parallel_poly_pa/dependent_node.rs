@@ -0,0 +1,224 @@
+//! Hosts of the node at which an epoch ends. Each draw of a node `v` of an epoch starting at `b`
+//! is split as follows: with probability `W / U` it samples from the state at `b` (total weight
+//! `W`), otherwise it hits the slack of the bound `U` used by the [`RunlengthSampler`]. The
+//! epoch continues with `v` iff all its draws sample from the state at `b`; hence these nodes
+//! are sampled in parallel. The node that ends the epoch, however, must not simply sample from
+//! the current state (total weight `W'`): conditioned on the failed trial, at least one of its
+//! draws hits the slack, which stands for the weight gained since `b` with probability
+//! `(W' - W) / (U - W)` and for a fresh draw from the current state otherwise. Sampling all of
+//! its draws from the current state would underweight the nodes that gained weight during the
+//! epoch.
+//!
+//! [`RunlengthSampler`]: super::run_length::RunlengthSampler
+
+use super::*;
+
+pub(crate) struct DependentNode {
+    /// Probability that a draw hits the slack, i.e. `1 - W / U`
+    prob_slack: f64,
+    /// Logarithm of `W / U`
+    log_prob_old: f64,
+    /// Probability that a draw hitting the slack samples from the weight gained since `b`
+    prob_gain: f64,
+}
+
+impl DependentNode {
+    /// `weight_before` is the total weight at the begin of the epoch that the node ended,
+    /// `weight_bound` the run length sampler's bound for the node, and `weight_now` the current
+    /// total weight
+    pub(crate) fn new(weight_before: f64, weight_bound: f64, weight_now: f64) -> Self {
+        let slack = (weight_bound - weight_before).max(0.0);
+        Self {
+            prob_slack: slack / weight_bound,
+            log_prob_old: -(slack / weight_before).ln_1p(),
+            prob_gain: if slack > 0.0 {
+                ((weight_now - weight_before) / slack).clamp(0.0, 1.0)
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Appends `number` hosts to `hosts`, which are distinct if `without_replacement`.
+    /// `sample_current` draws from the current state, `sample_gain` draws a node with probability
+    /// proportional to the weight it gained since `b`, and `old_fraction` returns the fraction of
+    /// a node's current weight it had at `b`. Errors of these functions (e.g. of remote
+    /// lookups) are passed on.
+    pub(crate) fn sample_hosts<R: Rng, E>(
+        &self,
+        rng: &mut R,
+        number: usize,
+        without_replacement: bool,
+        hosts: &mut Vec<Node>,
+        mut sample_current: impl FnMut(&mut R) -> Result<Node, E>,
+        mut sample_gain: impl FnMut(&mut R) -> Result<Node, E>,
+        mut old_fraction: impl FnMut(Node) -> Result<f64, E>,
+    ) -> Result<(), E> {
+        let begin = hosts.len();
+        let mut all_old = true;
+
+        for i in 0..number {
+            let hits_slack = if self.prob_slack <= 0.0 {
+                false
+            } else if all_old {
+                // conditioned on a hit among the remaining draws
+                let remaining = (number - i) as f64;
+                let prob_any = -(remaining * self.log_prob_old).exp_m1();
+                rng.gen_bool((self.prob_slack / prob_any).min(1.0))
+            } else {
+                rng.gen_bool(self.prob_slack)
+            };
+            all_old &= !hits_slack;
+
+            let host = loop {
+                let candidate = if !hits_slack {
+                    let candidate = sample_current(rng)?;
+                    if !rng.gen_bool(old_fraction(candidate)?.clamp(0.0, 1.0)) {
+                        continue;
+                    }
+                    candidate
+                } else if rng.gen_bool(self.prob_gain) {
+                    sample_gain(rng)?
+                } else {
+                    sample_current(rng)?
+                };
+
+                if !(without_replacement && hosts[begin..].contains(&candidate)) {
+                    break candidate;
+                }
+            };
+
+            hosts.push(host);
+        }
+
+        Ok(())
+    }
+}
+
+/// Nodes that gained weight in the previous epoch with the prefix sums of the gains. Only the
+/// hosts of one node per epoch look them up, hence they are kept in the order of the updates
+/// and [`Gains::gain`] searches linearly.
+#[derive(Default)]
+pub(crate) struct Gains {
+    prefix_sums: Vec<(Node, f64)>,
+}
+
+impl Gains {
+    /// Replaces the gains by `gains`, pairs of node and gained weight, and leaves `gains` empty
+    pub(crate) fn set(&mut self, gains: &mut Vec<(Node, f64)>) {
+        self.prefix_sums.clear();
+        let mut sum = 0.0;
+        for (node, gain) in gains.drain(..) {
+            sum += gain;
+            self.prefix_sums.push((node, sum));
+        }
+    }
+
+    pub(crate) fn total(&self) -> f64 {
+        self.prefix_sums.last().map_or(0.0, |g| g.1)
+    }
+
+    /// Returns the node whose gain covers `target`, which has to be less than [`Gains::total`]
+    pub(crate) fn node_at(&self, target: f64) -> Node {
+        let i = self.prefix_sums.partition_point(|g| g.1 <= target);
+        self.prefix_sums[i.min(self.prefix_sums.len() - 1)].0
+    }
+
+    /// Returns the sum of the gains of `node`
+    pub(crate) fn gain(&self, node: Node) -> f64 {
+        let mut previous_sum = 0.0;
+        let mut gain = 0.0;
+        for &(u, sum) in &self.prefix_sums {
+            if u == node {
+                gain += sum - previous_sum;
+            }
+            previous_sum = sum;
+        }
+        gain
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pcg_rand::Pcg64;
+    use rand::SeedableRng;
+    use std::convert::Infallible;
+
+    #[test]
+    fn gains_are_summed_per_node() {
+        let mut gains = Gains::default();
+        gains.set(&mut vec![(5, 1.0), (2, 0.5), (5, 2.0), (7, 0.5)]);
+
+        assert_eq!(gains.total(), 4.0);
+        assert_eq!([2, 5, 7, 3].map(|u| gains.gain(u)), [0.5, 3.0, 0.5, 0.0]);
+        assert_eq!(
+            [0.0, 0.99, 1.0, 1.49, 1.5, 3.49, 3.5, 3.99].map(|x| gains.node_at(x)),
+            [5, 5, 2, 2, 5, 5, 7, 7]
+        );
+    }
+
+    /// Together with the independent draws (probability `(W / U)^d`, all from the old state),
+    /// the hosts of the dependent node have to follow the current state
+    #[test]
+    fn mixture_with_independent_draws_is_current_state() {
+        const NUM_SAMPLES: usize = 400_000;
+        const NUMBER: usize = 2;
+
+        // node 2 gained weight 1 and node 3 is new, so W = 3, W' = 5; the bound is U = 6
+        let old = [1.0, 1.0, 1.0, 0.0];
+        let current = [1.0, 1.0, 2.0, 1.0];
+        let (weight_before, weight_bound, weight_now) = (3.0, 6.0, 5.0);
+
+        let sample = |weights: &[f64], rng: &mut Pcg64| {
+            let total: f64 = weights.iter().sum();
+            let mut x = rng.gen::<f64>() * total;
+            weights
+                .iter()
+                .position(|&w| {
+                    x -= w;
+                    x < 0.0
+                })
+                .unwrap_or(weights.len() - 1) as Node
+        };
+        let gain = [0.0, 0.0, 1.0, 1.0];
+
+        let dependent = DependentNode::new(weight_before, weight_bound, weight_now);
+        let prob_independent = (weight_before / weight_bound).powi(NUMBER as i32);
+
+        let mut rng = Pcg64::seed_from_u64(7);
+        let mut counts = [0usize; 4];
+        let mut hosts = Vec::new();
+        for _ in 0..NUM_SAMPLES {
+            hosts.clear();
+            if rng.gen_bool(prob_independent) {
+                hosts.extend((0..NUMBER).map(|_| sample(&old, &mut rng)));
+            } else {
+                let Ok(()) = dependent.sample_hosts::<_, Infallible>(
+                    &mut rng,
+                    NUMBER,
+                    false,
+                    &mut hosts,
+                    |rng| Ok(sample(&current, rng)),
+                    |rng| Ok(sample(&gain, rng)),
+                    |u| Ok(old[u as usize] / current[u as usize]),
+                );
+            }
+
+            for &host in &hosts {
+                counts[host as usize] += 1;
+            }
+        }
+
+        let num_draws = (NUM_SAMPLES * NUMBER) as f64;
+        for (u, &count) in counts.iter().enumerate() {
+            let p = current[u] / weight_now;
+            let sd = (num_draws * p * (1.0 - p)).sqrt();
+            assert!(
+                (count as f64 - p * num_draws).abs() < 5.0 * sd,
+                "{:?}",
+                counts
+            );
+        }
+    }
+}
@@ -1,14 +1,19 @@
 #![allow(clippy::too_many_arguments)]
 
+pub(crate) mod dependent_node;
+mod parallel_init;
 mod proposal_list;
-mod reports;
-mod run_length;
+pub(crate) mod reports;
+pub(crate) mod run_length;
+mod segmented_array;
 mod shared_state;
+pub mod telemetry;
 mod worker;
 
 use super::*;
-use proposal_list::ProposalList;
+use proposal_list::{ProposalList, Sampler};
 use shared_state::{NodeInfo, State};
+use telemetry::{NoTelemetry, Telemetry};
 use worker::Worker;
 
 use crate::algorithm::algo_parallel_poly_pa::run_length::RunlengthSampler;
@@ -20,17 +25,49 @@ use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::thread;
 
-use hurdles::Barrier;
+use crate::affinity::pin_current_thread;
+use crate::algorithm::seed_sequence::SeedSequence;
+use crate::barrier::{Barrier, SpinBarrier};
+use crate::parameters::Parameters;
+use std::marker::PhantomData;
+use std::path::PathBuf;
 
 const SCALE: f64 = 2.0 * (1u64 << 63) as f64;
 
-pub struct AlgoParallelPolyPa<R: Rng + Send + Sync> {
+/// Early epochs are too short to amortize the barriers between the phases, hence the main thread
+/// generates the graph sequentially (with the same proposal list and epoch ends) until the last
+/// `HANDOFF_WINDOW` epochs have an average length of at least `HANDOFF_NODES_PER_THREAD` nodes
+/// per thread; then the workers take over.
+const HANDOFF_WINDOW: usize = 8;
+const HANDOFF_NODES_PER_THREAD: Node = 16;
+
+/// The workers synchronize by barriers of type `B` and report per-epoch measurements to `T`
+/// (see [`telemetry`])
+pub struct AlgoParallelPolyPa<
+    R: Rng + Send + Sync,
+    B: Barrier = SpinBarrier,
+    T: Telemetry = NoTelemetry,
+> {
     rng: R,
     num_threads: usize,
+    /// CPU of each worker if threads are pinned
+    worker_cpus: Option<Vec<usize>>,
+    /// Destination of the telemetry records
+    telemetry_path: Option<PathBuf>,
+    /// Whether the workers test proposals in batches (see [`acceptance`](super::acceptance))
+    batched_acceptance: bool,
+    /// Master of the seed sequence; drawn from `rng` if not given
+    seed_sequence: Option<u64>,
+    /// Is `HANDOFF_NODES_PER_THREAD` except in tests, which hand off early
+    handoff_nodes_per_thread: Node,
     state: Arc<State>,
+    _barrier: PhantomData<B>,
+    _telemetry: PhantomData<T>,
 }
 
-impl<R: Rng + Send + Sync + SeedableRng + 'static> Algorithm<R> for AlgoParallelPolyPa<R> {
+impl<R: Rng + Send + Sync + SeedableRng + 'static, B: Barrier, T: Telemetry> Algorithm<R>
+    for AlgoParallelPolyPa<R, B, T>
+{
     const IS_PARALLEL: bool = true;
 
     fn new(
@@ -48,11 +85,17 @@ impl<R: Rng + Send + Sync + SeedableRng + 'static> Algorithm<R> for AlgoParallel
 
         println!("NodeInfo: {}b", std::mem::size_of::<NodeInfo>());
 
-        let runlength_sampler = RunlengthSampler::new(weight_function.clone(), initial_degree);
+        let runlength_sampler = RunlengthSampler::new(weight_function.clone(), initial_degree)
+            .unwrap_or_else(|e| panic!("{}", e));
 
         Self {
             rng,
             num_threads,
+            worker_cpus: None,
+            telemetry_path: None,
+            batched_acceptance: false,
+            seed_sequence: None,
+            handoff_nodes_per_thread: HANDOFF_NODES_PER_THREAD,
             state: Arc::new(State {
                 num_seed_nodes,
                 num_total_nodes,
@@ -61,67 +104,344 @@ impl<R: Rng + Send + Sync + SeedableRng + 'static> Algorithm<R> for AlgoParallel
                 weight_function,
 
                 total_weight: AtomicF64::new(0.0),
-                nodes: (0..num_total_nodes)
-                    .into_iter()
-                    .map(|_| NodeInfo::default())
-                    .collect(),
+                nodes: parallel_init::filled(
+                    num_total_nodes as usize,
+                    num_threads,
+                    NodeInfo::default,
+                ),
                 proposal_list: Arc::new(ProposalList::new(
-                    4 * num_total_nodes / 3 + 10000,
+                    4 * num_total_nodes as usize / 3 + 10000,
                     num_threads,
                 )),
                 runlength_sampler,
 
                 wmax: AtomicF64::new(0.0),
                 max_degree: AtomicCell::new(0),
+                next_unclaimed_node: AtomicCell::new(0),
+                gains: (0..num_threads).map(|_| Default::default()).collect(),
             }),
+            _barrier: PhantomData,
+            _telemetry: PhantomData,
         }
     }
 
+    fn from_parameters(rng: R, opt: &Parameters) -> Self {
+        let mut algo: Self = new_from_parameters(rng, opt);
+
+        // resolved before the setup, so that invalid CPUs are reported early
+        algo.worker_cpus = opt.pin_threads.as_ref().map(|pinning| {
+            let cpus = pinning
+                .cpus(algo.num_threads)
+                .unwrap_or_else(|e| panic!("Cannot pin threads by {:?}: {}", pinning, e));
+            println!("Pin threads to CPUs {:?}", cpus);
+            cpus
+        });
+        algo.telemetry_path = opt.telemetry.clone();
+        algo.batched_acceptance = opt.batched_acceptance;
+        algo.seed_sequence = opt.seed_sequence;
+
+        algo
+    }
+
+    /// The degrees are stored and the seed nodes pushed into the proposal list by `num_threads`
+    /// threads (see [`parallel_init`]), since seed graphs may be large
     fn set_seed_graph_degrees(&mut self, degrees: impl Iterator<Item = Node>) {
-        let mut num_input_degrees = 0;
+        let state = &*self.state;
+        let degrees = degrees.collect_vec();
+        assert_eq!(degrees.len(), state.num_seed_nodes as usize);
 
-        for (node, degree) in degrees.enumerate() {
-            self.state.sequential_set_degree(node, degree);
-            num_input_degrees += 1;
-        }
+        // per range: sum of weights, minimum and maximum degree
+        let summaries = parallel_init::map_ranges(degrees.len(), self.num_threads, |range| {
+            let mut summary = (0.0, Node::MAX, 0);
+            for (info, &degree) in state.nodes[range.clone()].iter().zip(&degrees[range]) {
+                let weight = state.weight_function.get(degree);
+                info.degree.store(degree);
+                info.weight.store(weight, Ordering::Relaxed);
 
-        assert_eq!(num_input_degrees, self.state.num_seed_nodes);
+                summary.0 += weight;
+                summary.1 = summary.1.min(degree);
+                summary.2 = summary.2.max(degree);
+            }
+            summary
+        });
 
-        for u in 0..self.state.num_seed_nodes {
-            self.state.sequential_update_node_counts_in_proposal_list(u);
-        }
+        let total_weight: f64 = summaries.iter().map(|s| s.0).sum();
+        state.total_weight.store(total_weight, Ordering::Release);
+        state
+            .max_degree
+            .store(summaries.iter().map(|s| s.2).max().unwrap());
+        state
+            .runlength_sampler
+            .set_min_seed_degree(summaries.iter().map(|s| s.1).min().unwrap());
 
-        self.state.runlength_sampler.setup_epoch(
-            self.state.num_seed_nodes,
-            self.state.num_total_nodes,
-            self.state.max_degree.load(),
-            self.state.total_weight.load(Ordering::Acquire),
+        let excesses = parallel_init::map_ranges(degrees.len(), self.num_threads, |range| {
+            range
+                .map(|u| state.update_node_counts_in_proposal_list(u as Node, state.num_seed_nodes))
+                .fold(0.0, f64::max)
+        });
+        state
+            .wmax
+            .fetch_max(excesses.into_iter().fold(0.0, f64::max), Ordering::AcqRel);
+
+        state.runlength_sampler.setup_epoch(
+            state.num_seed_nodes,
+            state.num_total_nodes,
+            state.max_degree.load(),
+            total_weight,
         );
     }
 
+    /// All random streams of the run are seeded from one sequence, whose master is printed;
+    /// passing it to `--seed-sequence` replays the run if it uses a single thread. With several
+    /// threads, the graph also depends on the order in which the workers claim nodes and push
+    /// into the proposal list, hence such runs are not reproducible.
     fn run(&mut self, _writer: &mut impl EdgeWriter) {
+        let mut seeds = SeedSequence::new(self.seed_sequence.unwrap_or_else(|| self.rng.gen()));
+        println!("seed_sequence:{:016x}", seeds.master());
+
+        self.rng = R::from_seed(seeds.next_seed::<R>());
+        let prefix_epoch_end_rng = R::from_seed(seeds.next_seed::<R>());
+        let first_parallel_node = self.run_sequential_prefix(
+            prefix_epoch_end_rng,
+            self.handoff_nodes_per_thread * self.num_threads as Node,
+        );
+
         let num_threads = self.num_threads; // needed for capture down below
-        let barrier = Barrier::new(num_threads);
+        let barrier = B::new(num_threads);
 
-        let handles = (0..self.num_threads)
+        // all workers draw the same epoch ends, but from a stream distinct from their own ones
+        let epoch_end_seed = seeds.next_seed::<R>().as_mut().to_vec();
+
+        let num_workers = if first_parallel_node < self.state.num_total_nodes {
+            self.num_threads
+        } else {
+            0
+        };
+
+        let handles = (0..num_workers)
             .into_iter()
             .map(|rank| {
                 let barrier = barrier.clone();
-                let rng = R::seed_from_u64(self.rng.gen()); // TODO: improve seeding
+                let rng = R::from_seed(seeds.next_seed::<R>());
+
+                let mut seed = R::Seed::default();
+                seed.as_mut().copy_from_slice(&epoch_end_seed);
+                let epoch_end_rng = R::from_seed(seed);
                 let state = self.state.clone();
+                let cpu = self.worker_cpus.as_ref().map(|cpus| cpus[rank]);
+                let batched_acceptance = self.batched_acceptance;
 
                 thread::spawn(move || {
-                    Worker::new(rng, state, barrier, rank, num_threads).run();
+                    // before the worker allocates its buffers, so they are local to its CPU
+                    if let Some(cpu) = cpu {
+                        pin_current_thread(cpu).unwrap_or_else(|e| {
+                            panic!("Cannot pin thread {} to CPU {}: {}", rank, cpu, e)
+                        });
+                    }
+
+                    let mut worker = Worker::<R, B, T>::new(
+                        rng,
+                        epoch_end_rng,
+                        state,
+                        barrier,
+                        rank,
+                        num_threads,
+                        first_parallel_node,
+                        batched_acceptance,
+                    );
+                    worker.run();
+                    worker.into_telemetry_records()
                 })
             })
             .collect_vec();
 
-        for handle in handles {
-            handle.join().unwrap();
+        let records = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect_vec();
+
+        if let Some(path) = self.telemetry_path.as_ref() {
+            telemetry::write_csv(path, records)
+                .unwrap_or_else(|e| panic!("Cannot write telemetry to {}: {}", path.display(), e));
         }
+
+        let proposal_list = &self.state.proposal_list;
+        println!("Proposal list length: {}", proposal_list.len());
+        println!("Compactions: {}", proposal_list.num_compactions());
+        println!("Holes compacted: {}", proposal_list.num_compacted_holes());
+        println!("Holes remaining: {}", proposal_list.count_holes());
     }
 
     fn degrees(&self) -> Vec<Node> {
         self.state.nodes.iter().map(|i| i.degree.load()).collect()
     }
 }
+
+impl<R: Rng + Send + Sync + SeedableRng + 'static, B: Barrier, T: Telemetry>
+    AlgoParallelPolyPa<R, B, T>
+{
+    /// Inserts nodes one at a time until the last `HANDOFF_WINDOW` epochs have an average length
+    /// of at least `min_epoch_len`; the epoch ends are drawn as by the workers only to observe
+    /// their lengths, but from `epoch_end_rng`. Returns the first node left to the workers.
+    fn run_sequential_prefix(&mut self, mut epoch_end_rng: R, min_epoch_len: Node) -> Node {
+        let state = self.state.clone();
+
+        let mut sampler = Sampler::new(state.proposal_list.clone());
+        let mut hosts = Vec::with_capacity(state.initial_degree as usize);
+
+        let mut epoch_lengths = [0 as Node; HANDOFF_WINDOW];
+        let mut num_epochs = 0;
+        let mut epoch_end = state.num_seed_nodes;
+
+        while epoch_end < state.num_total_nodes {
+            let epoch_begin = epoch_end;
+            epoch_end = state.runlength_sampler.sample_epoch_end(
+                &mut epoch_end_rng,
+                state.initial_degree as usize,
+                state.num_total_nodes,
+            );
+
+            for new_node in epoch_begin..epoch_end {
+                self.sequential_insert_node(&mut sampler, &mut hosts, new_node);
+            }
+
+            state.runlength_sampler.setup_epoch(
+                epoch_end,
+                state.num_total_nodes,
+                state.max_degree.load(),
+                state.total_weight.load(Ordering::Acquire),
+            );
+
+            epoch_lengths[num_epochs % HANDOFF_WINDOW] = epoch_end - epoch_begin;
+            num_epochs += 1;
+
+            if num_epochs >= HANDOFF_WINDOW
+                && epoch_lengths.iter().sum::<Node>()
+                    >= min_epoch_len.saturating_mul(HANDOFF_WINDOW as Node)
+            {
+                break;
+            }
+        }
+
+        println!(
+            "Sequential prefix: {} nodes in {} epochs",
+            epoch_end - state.num_seed_nodes,
+            num_epochs
+        );
+
+        epoch_end
+    }
+
+    /// Connects `new_node` to `initial_degree` distinct hosts sampled as by the workers, but
+    /// updates the degrees and the proposal list right away
+    fn sequential_insert_node(
+        &mut self,
+        sampler: &mut Sampler,
+        hosts: &mut Vec<Node>,
+        new_node: Node,
+    ) {
+        let state = &*self.state;
+        let wmax_scaled = SCALE / state.wmax.load(Ordering::Acquire);
+
+        sampler.update(&mut self.rng, new_node);
+
+        hosts.clear();
+        while hosts.len() < state.initial_degree as usize {
+            let proposal = sampler.sample(&mut self.rng);
+            if hosts.contains(&proposal) {
+                continue;
+            }
+
+            let info = &state.nodes[proposal as usize];
+            let excess = info.weight.load(Ordering::Acquire) / info.count.load() as f64;
+
+            if self.rng.gen::<u64>() < (excess * wmax_scaled) as u64 {
+                hosts.push(proposal);
+            }
+        }
+
+        state.sequential_set_degree(new_node, state.initial_degree);
+        for &host in hosts.iter() {
+            state.sequential_increase_degree(host);
+        }
+
+        for &node in hosts.iter().chain(std::iter::once(&new_node)) {
+            state.sequential_update_node_counts_in_proposal_list(node, new_node + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::test_util::Setup;
+    use crate::barrier::StdBarrier;
+    use crate::edge_writer::EdgeCounter;
+    use pcg_rand::Pcg64;
+
+    fn setup(exponent: f64) -> Setup {
+        Setup {
+            repeats: 200,
+            num_seed_nodes: 4,
+            num_rand_nodes: 5000,
+            initial_degree: 2,
+            exponent,
+        }
+    }
+
+    /// The prefix never hands off here and has to be exact
+    #[test]
+    fn sequential_prefix_matches_dynamic_weighted_index() {
+        for exponent in [0.5, 1.0] {
+            let setup = setup(exponent);
+            setup.assert_matches_dynamic_weighted_index(|seed| {
+                let mut algo = setup.new_algo::<AlgoParallelPolyPa<_>>(seed, 1);
+                let epoch_end_rng = Pcg64::from_seed(SeedSequence::new(seed).next_seed::<Pcg64>());
+                assert_eq!(algo.run_sequential_prefix(epoch_end_rng, Node::MAX), 5004);
+                algo.degrees()
+            });
+        }
+    }
+
+    /// The workers take over after `HANDOFF_WINDOW` epochs, so nearly all nodes are inserted in
+    /// parallel epochs, each starting with a node that ended the previous one
+    #[test]
+    fn early_handoff_matches_dynamic_weighted_index() {
+        for (exponent, num_threads) in [(0.5, 2), (1.0, 3), (1.0, 4)] {
+            let setup = setup(exponent);
+            setup.assert_matches_dynamic_weighted_index(|seed| {
+                let mut algo =
+                    setup.new_algo::<AlgoParallelPolyPa<_, StdBarrier>>(seed, num_threads);
+                algo.handoff_nodes_per_thread = 0;
+                algo.run(&mut EdgeCounter::default());
+                algo.degrees()
+            });
+        }
+    }
+
+    #[test]
+    fn single_thread_is_reproducible() {
+        let degrees = [0, 1].map(|_| {
+            let mut algo = setup(1.0).new_algo::<AlgoParallelPolyPa<_, StdBarrier>>(1, 1);
+            algo.handoff_nodes_per_thread = 0;
+            algo.run(&mut EdgeCounter::default());
+            algo.degrees()
+        });
+
+        assert_eq!(degrees[0], degrees[1]);
+    }
+
+    #[test]
+    fn seed_sequence_replays_single_thread() {
+        let degrees = [0, 1, 2].map(|seed| {
+            let mut algo = setup(1.0).new_algo::<AlgoParallelPolyPa<_, StdBarrier>>(seed, 1);
+            algo.seed_sequence = Some(if seed < 2 { 0x1234 } else { 0x1235 });
+            algo.run(&mut EdgeCounter::default());
+            algo.degrees()
+        });
+
+        // the generator passed to `new` does not matter
+        assert_eq!(degrees[0], degrees[1]);
+        assert_ne!(degrees[0], degrees[2]);
+    }
+}
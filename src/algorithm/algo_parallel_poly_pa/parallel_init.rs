@@ -0,0 +1,93 @@
+//! Initialization of large arrays by several threads. Besides the speed-up, the pages of an
+//! array are first touched (and hence, under the usual first-touch policy, placed) by the
+//! threads of different NUMA regions rather than all by the main thread.
+
+use std::mem::MaybeUninit;
+use std::ops::Range;
+use std::thread;
+
+/// Below this number of elements per thread, spawning a thread does not pay off
+const MIN_CHUNK_LEN: usize = 1 << 16;
+
+fn chunk_len(len: usize, num_threads: usize) -> usize {
+    len.div_ceil(num_threads.max(1)).max(MIN_CHUNK_LEN)
+}
+
+/// Splits `0..len` into at most `num_threads` consecutive ranges and returns the results of `f`
+/// applied to each of them, in order. The first range is processed by the calling thread.
+pub(super) fn map_ranges<T, F>(len: usize, num_threads: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(Range<usize>) -> T + Sync,
+{
+    let chunk_len = chunk_len(len, num_threads);
+    let f = &f;
+
+    thread::scope(|s| {
+        let handles = (chunk_len..len)
+            .step_by(chunk_len)
+            .map(|begin| s.spawn(move || f(begin..len.min(begin + chunk_len))))
+            .collect::<Vec<_>>();
+
+        let first = f(0..len.min(chunk_len));
+
+        std::iter::once(first)
+            .chain(handles.into_iter().map(|h| h.join().unwrap()))
+            .collect()
+    })
+}
+
+/// Returns a vector of `len` elements produced by `init`, which are written by up to
+/// `num_threads` threads
+pub(super) fn filled<T, F>(len: usize, num_threads: usize, init: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn() -> T + Sync,
+{
+    let mut vec = Vec::with_capacity(len);
+    let chunk_len = chunk_len(len, num_threads);
+    let init = &init;
+
+    thread::scope(|s| {
+        let mut chunks = vec.spare_capacity_mut()[..len].chunks_mut(chunk_len);
+        let first = chunks.next();
+
+        for chunk in chunks {
+            s.spawn(move || write_all(chunk, init));
+        }
+
+        if let Some(chunk) = first {
+            write_all(chunk, init);
+        }
+    });
+
+    // safety: the threads initialized all `len` elements before the scope ended
+    unsafe { vec.set_len(len) };
+    vec
+}
+
+fn write_all<T>(chunk: &mut [MaybeUninit<T>], init: impl Fn() -> T) {
+    for x in chunk {
+        x.write(init());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranges_cover_input_in_order() {
+        for len in [0, 1, MIN_CHUNK_LEN, 3 * MIN_CHUNK_LEN + 1] {
+            for num_threads in [1, 2, 5] {
+                let ranges = map_ranges(len, num_threads, |r| r);
+                assert!(ranges.len() <= num_threads.max(1));
+                assert_eq!(ranges.first().unwrap().start, 0);
+                assert_eq!(ranges.last().unwrap().end, len);
+                assert!(ranges.windows(2).all(|w| w[0].end == w[1].start));
+
+                assert_eq!(filled(len, num_threads, || 7u8), vec![7u8; len]);
+            }
+        }
+    }
+}
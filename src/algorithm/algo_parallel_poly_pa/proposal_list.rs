@@ -1,13 +1,19 @@
 #![allow(dead_code)]
 
+use super::segmented_array::SegmentedArray;
 use super::*;
 use crossbeam::atomic::AtomicCell;
 use itertools::Itertools;
-use std::intrinsics::likely;
+use rand_distr::{Distribution, Geometric};
+use ringbuffer::{
+    ConstGenericRingBuffer, RingBuffer, RingBufferExt, RingBufferRead, RingBufferWrite,
+};
+use std::intrinsics::{likely, prefetch_read_data};
 use std::ops::Range;
 
 const UNINITIALIZED: Node = Node::MAX;
 const BLOCK_SIZE: usize = 32;
+const PREFETCH_LEN: usize = 8;
 
 struct AtomicBlockInfo {
     begin: AtomicCell<usize>,
@@ -30,20 +36,19 @@ impl AtomicBlockInfo {
 }
 
 pub(super) struct ProposalList {
-    proposal_list: Vec<AtomicCell<Node>>,
+    proposal_list: SegmentedArray,
     begin_of_next_block: AtomicCell<usize>,
     unfinished_blocks: Vec<AtomicBlockInfo>,
     producer_id: AtomicCell<usize>,
+    num_compactions: AtomicCell<usize>,
+    num_compacted_holes: AtomicCell<usize>,
 }
 
 impl ProposalList {
+    /// `size` is only a hint for the initial capacity; the list grows as needed
     pub fn new(size: usize, num_threads: usize) -> Self {
         let n = size + 10 * num_threads * BLOCK_SIZE * ((size as f64).sqrt().ceil() as usize);
-
-        let mut proposal_list = Vec::with_capacity(n);
-        for _ in 0..n {
-            proposal_list.push(AtomicCell::new(UNINITIALIZED));
-        }
+        let proposal_list = SegmentedArray::new(n, UNINITIALIZED, num_threads);
 
         let unfinished_blocks = (0..num_threads).map(|_| Default::default()).collect();
 
@@ -52,157 +57,221 @@ impl ProposalList {
             unfinished_blocks,
             begin_of_next_block: AtomicCell::new(0),
             producer_id: AtomicCell::new(0),
+            num_compactions: AtomicCell::new(0),
+            num_compacted_holes: AtomicCell::new(0),
         }
     }
 
-    pub fn unbuffered_push(&self, node: Node, mut count: usize) {
-        while count > 0 {
-            self.proposal_list[self.begin_of_next_block.fetch_add(1)].store(node);
-            count -= 1;
+    /// Appends `count` copies of `node`; may run concurrently with other unbuffered pushes
+    pub fn unbuffered_push(&self, node: Node, count: usize) {
+        let begin = self.begin_of_next_block.fetch_add(count);
+        self.proposal_list.ensure_len(begin + count);
+        for index in begin..begin + count {
+            self.proposal_list[index].store(node);
         }
     }
 
+    /// Moves the entries behind the unfinished ranges (freed by the writers) into them, so that
+    /// the list has no holes afterwards; returns the new end of the list. Must not run
+    /// concurrently with any other access to the list.
     pub fn compact_unfinished_ranges(&self) -> usize {
-        // we cannot borrow self as mut (as shared between threads) and work on a (non-atomic) copy
-        let mut gap_list = self
+        let gap_list = self
             .unfinished_blocks
             .iter()
-            .map(|b| b.get_range())
+            .map(|b| {
+                let range = b.get_range();
+                b.begin.store(0);
+                b.end.store(0);
+                range
+            })
             .filter(|r| !r.is_empty())
             .collect_vec();
 
-        if gap_list.is_empty() {
-            return self.begin_of_next_block.load();
-        }
-
-        gap_list.sort_unstable_by_key(|r| -(r.start as isize));
+        let end_of_data = self.begin_of_next_block.load();
+        let num_holes: usize = gap_list.iter().map(|r| r.len()).sum();
+        let end = end_of_data - num_holes;
 
-        let active_range =
-            gap_list.last().unwrap().start.saturating_sub(1)..gap_list.first().unwrap().end;
+        // ensure all gaps are uninitialized
+        debug_assert!(gap_list.iter().all(|r| r
+            .clone()
+            .all(|i| self.proposal_list[i].load() == UNINITIALIZED)));
 
-        let mut set_list = gap_list
+        // the number of holes before `end` matches the number of entries behind it
+        let targets = gap_list
             .iter()
-            .rev()
-            .tuple_windows()
-            .map(|(pred, suc)| pred.end..suc.start)
-            .collect_vec();
+            .flat_map(|r| r.start.min(end)..r.end.min(end));
+
+        let sources = (end..end_of_data).filter(|&i| self.proposal_list[i].load() != UNINITIALIZED);
 
-        if gap_list.first().unwrap().end != self.begin_of_next_block.load() {
-            // the proposal lists ends with valid data
-            set_list.push(gap_list.first().unwrap().end..self.begin_of_next_block.load());
+        for (target, source) in targets.zip(sources) {
+            let value = self.proposal_list[source].swap(UNINITIALIZED);
+            self.proposal_list[target].store(value);
         }
 
-        // uninit_blocks is sorted decreasingly, i.e. the "left-most" block in proposal list is at the back
-        // init_blocks is sorted increasingly. We chose this order since we will pop from the back
-        let end = self.compact_from_lists(&mut gap_list, &mut set_list);
-
-        // ensure compaction worked: all elements up to end are initilized; all remaining are uninitialized
-        debug_assert!(self.proposal_list[active_range.start..end]
-            .iter()
-            .all(|p| p.load() != UNINITIALIZED));
-
-        debug_assert!(self.proposal_list[end..active_range.end]
-            .iter()
-            .all(|p| p.load() == UNINITIALIZED));
+        // ensure compaction worked: all entries behind end are uninitialized
+        debug_assert!((end..end_of_data).all(|i| self.proposal_list[i].load() == UNINITIALIZED));
+        debug_assert!(gap_list.iter().all(|r| (r.start.min(end)..r.end.min(end))
+            .all(|i| self.proposal_list[i].load() != UNINITIALIZED)));
 
         self.begin_of_next_block.store(end);
+        self.num_compactions.fetch_add(1);
+        self.num_compacted_holes.fetch_add(num_holes);
 
         end
     }
 
-    fn compact_from_lists(
-        &self,
-        gap_list: &mut Vec<Range<usize>>,
-        set_list: &mut Vec<Range<usize>>,
-    ) -> usize {
-        // ensure all "gaps" are uninitialized and "sets" are initilized
-        debug_assert!(gap_list.iter().all(|r| self.proposal_list[r.clone()]
-            .iter()
-            .all(|p| p.load() == UNINITIALIZED)));
-
-        debug_assert!(set_list.iter().all(|r| self.proposal_list[r.clone()]
-            .iter()
-            .all(|p| p.load() != UNINITIALIZED)));
-
-        if set_list.is_empty() {
-            return gap_list.first().unwrap().start;
-        }
-
-        let mut set_range = set_list.pop().unwrap();
-        'compacting: loop {
-            if gap_list.len() == 1 {
-                break gap_list.first().unwrap().start.min(set_range.end);
-            }
-
-            let mut gap_range = gap_list.pop().unwrap();
-
-            if set_range.end < gap_range.start {
-                break 'compacting set_range.end;
-            }
-
-            loop {
-                if set_range.is_empty() {
-                    match set_list.pop() {
-                        Some(b) if b.start >= gap_range.end => set_range = b,
-                        _ => break 'compacting gap_range.start,
-                    }
-                }
-
-                set_range.end -= 1;
+    /// Number of calls to [`ProposalList::compact_unfinished_ranges`] so far
+    pub fn num_compactions(&self) -> usize {
+        self.num_compactions.load()
+    }
 
-                let value = self.proposal_list[set_range.end].load();
-                debug_assert!(value != UNINITIALIZED);
-                self.proposal_list[gap_range.start].store(value);
-                self.proposal_list[set_range.end].store(UNINITIALIZED);
+    /// Number of holes removed by [`ProposalList::compact_unfinished_ranges`] so far
+    pub fn num_compacted_holes(&self) -> usize {
+        self.num_compacted_holes.load()
+    }
 
-                gap_range.start += 1;
+    /// Counts the holes in the list in time linear in its length
+    pub fn count_holes(&self) -> usize {
+        (0..self.begin_of_next_block.load())
+            .filter(|&i| self.proposal_list[i].load() == UNINITIALIZED)
+            .count()
+    }
 
-                if gap_range.is_empty() {
-                    break;
-                }
-            }
-        }
+    pub fn len(&self) -> usize {
+        self.begin_of_next_block.load()
     }
 }
 
+/// Samples uniformly from the entries of the proposal list followed by the nodes `0..num_nodes`
+/// (each node once), skipping holes. Like the sampler of
+/// [`AlgoPolyPaPrefetch`](crate::algorithm::algo_poly_pa_prefetch::AlgoPolyPaPrefetch), it draws
+/// `PREFETCH_LEN` indices ahead of time and prefetches their entries, since the accesses to the
+/// list are bound by memory latency for large graphs. The list comes first, so that the indices
+/// kept by [`Sampler::update`] still refer to the entries they prefetched, as the list only
+/// grows at its end (except for compactions).
 pub(super) struct Sampler {
     proposal_list: Arc<ProposalList>,
+    num_nodes: usize,
     end: usize,
+    index_buffer: ConstGenericRingBuffer<usize, PREFETCH_LEN>,
+    /// Number of elements the indices in the buffer were drawn from
+    prefetched_size: usize,
 }
 
 impl Sampler {
     pub(super) fn new(proposal_list: Arc<ProposalList>) -> Self {
         let end = proposal_list.begin_of_next_block.load();
-        Self { proposal_list, end }
+        Self {
+            proposal_list,
+            num_nodes: 0,
+            end,
+            index_buffer: Default::default(),
+            prefetched_size: 0,
+        }
     }
 
-    pub fn sample(&self, rng: &mut impl Rng, new_node: Node) -> Node {
+    /// Samples from the nodes `0..num_nodes` and the proposal list up to its current end. Must
+    /// not run concurrently with writers, and has to be called after each change of the list.
+    pub fn update(&mut self, rng: &mut impl Rng, num_nodes: Node) {
+        self.num_nodes = num_nodes as usize;
+        self.end = self.proposal_list.begin_of_next_block.load();
+
+        let elements = self.num_elements();
+        if self.index_buffer.is_empty() {
+            self.prefetch(rng);
+        } else if elements > self.prefetched_size {
+            self.resample_after_growth(rng);
+        } else if elements < self.prefetched_size {
+            self.resample_after_shrinking(rng);
+        }
+    }
+
+    pub fn sample(&mut self, rng: &mut impl Rng) -> Node {
         loop {
-            let index = rng.gen_range(0..self.end + new_node);
+            let index = self.index_buffer.dequeue().unwrap();
+            self.prefetch(rng);
 
-            if index < new_node {
-                break index;
+            if index >= self.end {
+                break (index - self.end) as Node;
             }
 
-            let proposal = unsafe {
-                self.proposal_list
-                    .proposal_list
-                    .get_unchecked(index - new_node)
-            }
-            .load();
+            // safety: all indices below end were allocated by writers before the last barrier
+            let proposal = unsafe { self.proposal_list.proposal_list.get_unchecked(index) }.load();
             if likely(proposal != UNINITIALIZED) {
                 break proposal;
             }
         }
     }
 
+    fn num_elements(&self) -> usize {
+        self.num_nodes + self.end
+    }
+
+    fn prefetch(&mut self, rng: &mut impl Rng) {
+        let elements = self.num_elements();
+        self.prefetched_size = elements;
+
+        while !self.index_buffer.is_full() {
+            let index = rng.gen_range(0..elements);
+            self.prefetch_entry(index);
+            self.index_buffer.push(index);
+        }
+    }
+
+    /// An index drawn from the old elements is kept with the probability that a uniform index
+    /// into all elements falls into the old ones, and replaced by a new element otherwise; we
+    /// skip over the kept indices with a geometric distribution.
+    fn resample_after_growth(&mut self, rng: &mut impl Rng) {
+        let elements = self.num_elements();
+        let geometric =
+            Geometric::new(1.0 - self.prefetched_size as f64 / elements as f64).unwrap();
+
+        let mut buffer_index = 0;
+        loop {
+            buffer_index += geometric.sample(rng) as usize;
+            if buffer_index >= self.index_buffer.len() {
+                break;
+            }
+
+            let index = rng.gen_range(self.prefetched_size..elements);
+            self.prefetch_entry(index);
+            *self.index_buffer.get_mut(buffer_index as isize).unwrap() = index;
+            buffer_index += 1;
+        }
+
+        self.prefetched_size = elements;
+    }
+
+    /// The indices below the new number of elements remain uniform, the others are redrawn
+    fn resample_after_shrinking(&mut self, rng: &mut impl Rng) {
+        let elements = self.num_elements();
+
+        for i in 0..self.index_buffer.len() {
+            if *self.index_buffer.get(i as isize).unwrap() >= elements {
+                let index = rng.gen_range(0..elements);
+                self.prefetch_entry(index);
+                *self.index_buffer.get_mut(i as isize).unwrap() = index;
+            }
+        }
+
+        self.prefetched_size = elements;
+    }
+
     #[inline]
-    pub fn update_end(&mut self) {
-        self.end = self.proposal_list.begin_of_next_block.load();
+    fn prefetch_entry(&self, index: usize) {
+        if index < self.end {
+            // safety: all indices below end were allocated by writers before the last barrier
+            unsafe {
+                prefetch_read_data(
+                    self.proposal_list.proposal_list.get_unchecked(index)
+                        as *const AtomicCell<Node>,
+                    1,
+                );
+            }
+        }
     }
 
     #[inline]
-    #[allow(dead_code)]
     pub fn end(&self) -> usize {
         self.end
     }
@@ -238,8 +307,8 @@ impl Writer {
 
             let this_count = count.min(self.end - self.begin);
             for _ in 0..this_count {
-                // this access is safe, since we checked the validity in fetch_new_range and
-                // the Arc prevents modification of the vector size
+                // this access is safe, since fetch_new_range ensured the range is allocated and
+                // segments are never freed while the Arc is alive
                 unsafe { self.proposal_list.proposal_list.get_unchecked(self.begin) }.store(node);
                 self.begin += 1;
             }
@@ -248,6 +317,8 @@ impl Writer {
         }
     }
 
+    /// Hands the remainder of the current block to [`ProposalList::compact_unfinished_ranges`];
+    /// the next push fetches a new block.
     pub(super) fn free_unfinished_range(&mut self) {
         let info = &self.proposal_list.unfinished_blocks[self.producer_id];
         info.begin.store(self.begin);
@@ -258,8 +329,8 @@ impl Writer {
 
     fn fetch_new_range(&mut self) {
         self.begin = self.proposal_list.begin_of_next_block.fetch_add(BLOCK_SIZE);
-        assert!(self.begin + BLOCK_SIZE < self.proposal_list.proposal_list.len());
         self.end = self.begin + BLOCK_SIZE;
+        self.proposal_list.proposal_list.ensure_len(self.end);
     }
 }
 
@@ -268,6 +339,8 @@ mod test {
     use super::*;
     use pcg_rand::Pcg64;
     use rand::prelude::IteratorRandom;
+    // the tests share it through an `Arc`, so it has to wait through `&self`
+    use std::sync::Barrier;
 
     fn run_n_threads<F>(size: usize, num_threads: usize, callback: Arc<F>)
     where
@@ -310,7 +383,7 @@ mod test {
 
                     inds.push(elements_to_push);
 
-                    let mut i = elements_to_push * rank;
+                    let mut i = (elements_to_push * rank) as Node;
 
                     let mut producer = Writer::new(proposal_list.clone());
 
@@ -336,6 +409,70 @@ mod test {
 
     const SIZES: [usize; 12] = [10, 20, 30, 40, 50, 100, 200, 300, 400, 500, 1000, 10000];
 
+    /// Asserts that the first `PREFETCH_LEN` samples after each update (which stem from the kept
+    /// or resampled indices) are uniform over the entries and the nodes. Between the updates, the
+    /// list grows with new nodes, and shrinks by a compaction of the writer's unfinished block.
+    #[test]
+    fn sampler_remains_uniform_after_growth_and_compaction() {
+        const NUM_TRIALS: usize = 20000;
+        // entries are numbered from `FIRST_ENTRY` to tell them apart from the nodes
+        const FIRST_ENTRY: Node = 1000;
+
+        let phases: [(Node, usize); 3] = [(10, 20), (25, 50), (40, 0)];
+        let mut counts = vec![vec![0usize; 2 * FIRST_ENTRY as usize]; phases.len()];
+
+        let mut rng = Pcg64::seed_from_u64(4);
+        for _ in 0..NUM_TRIALS {
+            let proposal_list = Arc::new(ProposalList::new(100, 1));
+            let mut writer = Writer::new(proposal_list.clone());
+            let mut sampler = Sampler::new(proposal_list.clone());
+            let mut next_entry = FIRST_ENTRY;
+
+            for (phase, &(num_nodes, num_entries)) in phases.iter().enumerate() {
+                if num_entries > 0 {
+                    for _ in 0..num_entries {
+                        writer.push(next_entry, 1);
+                        next_entry += 1;
+                    }
+                } else {
+                    writer.free_unfinished_range();
+                    proposal_list.compact_unfinished_ranges();
+                }
+
+                sampler.update(&mut rng, num_nodes);
+                for _ in 0..PREFETCH_LEN {
+                    counts[phase][sampler.sample(&mut rng) as usize] += 1;
+                }
+            }
+        }
+
+        let mut num_entries = 0;
+        for (phase, &(num_nodes, new_entries)) in phases.iter().enumerate() {
+            num_entries += new_entries;
+            let elements = (0..num_nodes).chain(FIRST_ENTRY..FIRST_ENTRY + num_entries as Node);
+
+            let p = 1.0 / (num_nodes as usize + num_entries) as f64;
+            let expected = (NUM_TRIALS * PREFETCH_LEN) as f64 * p;
+            let sd = (expected * (1.0 - p)).sqrt();
+
+            let counts = &counts[phase];
+            assert_eq!(
+                elements.clone().map(|u| counts[u as usize]).sum::<usize>(),
+                NUM_TRIALS * PREFETCH_LEN
+            );
+            for u in elements {
+                assert!(
+                    (counts[u as usize] as f64 - expected).abs() < 5.0 * sd,
+                    "phase {}: element {} drawn {} times, expected {}",
+                    phase,
+                    u,
+                    counts[u as usize],
+                    expected
+                );
+            }
+        }
+    }
+
     #[test]
     fn randomized_seq() {
         for size in SIZES {
@@ -3,21 +3,24 @@ use std::intrinsics::likely;
 use std::ops::Range;
 use std::time::Instant;
 
-pub(super) struct Reporter {
+pub(crate) struct Reporter {
     start: Instant,
     last_report: Instant,
 
     num_total_nodes: Node,
 
-    epoch_id: Node,
+    epoch_id: usize,
     epoch_nodes: Range<Node>,
 
     last_report_ended: Node,
-    last_report_epoch_id: Node,
+    last_report_epoch_id: usize,
+
+    num_epochs: usize,
+    first_epoch_begin: Option<Node>,
 }
 
 impl Reporter {
-    pub(super) fn new(num_total_nodes: Node) -> Self {
+    pub(crate) fn new(num_total_nodes: Node) -> Self {
         let now = Instant::now();
         Self {
             start: now,
@@ -29,15 +32,21 @@ impl Reporter {
 
             last_report_ended: 0,
             last_report_epoch_id: 0,
+
+            num_epochs: 0,
+            first_epoch_begin: None,
         }
     }
 
-    pub(super) fn update_epoch(&mut self, epoch_id: Node, epoch_nodes: Range<Node>) {
+    pub(crate) fn update_epoch(&mut self, epoch_id: usize, epoch_nodes: Range<Node>) {
+        self.first_epoch_begin.get_or_insert(epoch_nodes.start);
+        self.num_epochs += 1;
+
         self.epoch_id = epoch_id;
         self.epoch_nodes = epoch_nodes;
     }
 
-    pub(super) fn report_progress_sometimes(&mut self) {
+    pub(crate) fn report_progress_sometimes(&mut self) {
         let now = Instant::now();
         let duration = now.duration_since(self.last_report);
 
@@ -48,9 +57,18 @@ impl Reporter {
         self.report_progress_now(now);
     }
 
-    pub(super) fn report_progress_forced(&mut self) {
+    /// Also reports the average epoch length over the whole run
+    pub(crate) fn report_progress_forced(&mut self) {
         let now = Instant::now();
         self.report_progress_now(now);
+
+        if let Some(first_epoch_begin) = self.first_epoch_begin {
+            println!(
+                "Epochs: {}; average length: {:.1}",
+                self.num_epochs,
+                (self.epoch_nodes.end - first_epoch_begin) as f64 / self.num_epochs as f64
+            );
+        }
     }
 
     fn report_progress_now(&mut self, now: Instant) {
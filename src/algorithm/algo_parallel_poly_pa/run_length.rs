@@ -1,9 +1,10 @@
 use super::*;
-use crate::weight_function::Regime;
+use crate::weight_function::{Curvature, KernelProperties};
+use rand_distr::{Distribution, Geometric};
 
-const BLOCK_LEN: usize = 100;
+const BLOCK_LEN: Node = 100;
 
-pub(super) struct RunlengthSampler {
+pub(crate) struct RunlengthSampler {
     weight_function: WeightFunction,
     initial_degree: Node,
 
@@ -15,17 +16,41 @@ pub(super) struct RunlengthSampler {
 
     real_lower: AtomicCell<Node>,
 
+    properties: KernelProperties,
     weight_initial_degree: f64,
     weight_max_degree: AtomicF64,
+
+    /// Bounds the weight increase of a node gaining one edge for concave and linear kernels
+    weight_increase_per_host: AtomicF64,
 }
 
 impl RunlengthSampler {
-    pub(super) fn new(weight_function: WeightFunction, initial_degree: Node) -> Self {
+    /// The bounds assume that the weight of a node never decreases (otherwise the hosts of a node
+    /// are not distributed as sampled from the state at the begin of the epoch); other kernels
+    /// are rejected.
+    pub(crate) fn new(
+        weight_function: WeightFunction,
+        initial_degree: Node,
+    ) -> Result<Self, String> {
+        let properties = weight_function.properties();
+        if !properties.is_non_decreasing() {
+            return Err(format!(
+                "Kernel {:?} is not supported by the parallel algorithms; weights must not decrease",
+                properties
+            ));
+        }
+
         let weight_initial_degree = weight_function.get(initial_degree);
+        // valid for all degrees, since the increments are largest at degree zero
+        let weight_increase_per_host = match properties.curvature {
+            Curvature::Concave | Curvature::Linear => weight_function.max_increment(0..1),
+            Curvature::Convex => f64::NAN,
+        };
 
-        Self {
+        Ok(Self {
             weight_function,
             initial_degree,
+            properties,
             weight_initial_degree,
 
             total_weight: Default::default(),
@@ -34,10 +59,28 @@ impl RunlengthSampler {
             upper: Default::default(),
             real_lower: Default::default(),
             weight_max_degree: Default::default(),
+            weight_increase_per_host: AtomicF64::new(weight_increase_per_host),
+        })
+    }
+
+    /// Tightens the bound of concave kernels given the minimum degree of the seed graph.
+    /// Since degrees never decrease and new nodes have the initial degree, the minimum of both
+    /// bounds the degree of every host in all later epochs; by concavity of the weight function,
+    /// the increase caused by an additional edge is the largest at the minimum degree.
+    pub(crate) fn set_min_seed_degree(&self, min_seed_degree: Node) {
+        if self.properties.curvature == Curvature::Convex {
+            return;
         }
+
+        let min_degree = min_seed_degree.min(self.initial_degree);
+        self.weight_increase_per_host.store(
+            self.weight_function
+                .max_increment(min_degree..min_degree + 1),
+            Ordering::Release,
+        );
     }
 
-    pub(super) fn setup_epoch(
+    pub(crate) fn setup_epoch(
         &self,
         lower: Node,
         upper: Node,
@@ -58,7 +101,7 @@ impl RunlengthSampler {
     }
 
     #[allow(dead_code)]
-    pub(super) fn sample(&self, rng: &mut impl Rng) {
+    pub(crate) fn sample(&self, rng: &mut impl Rng) {
         loop {
             let start_node = self.lower.fetch_add(BLOCK_LEN);
             let upper = self.upper.load();
@@ -67,7 +110,7 @@ impl RunlengthSampler {
             }
 
             for node in start_node..upper.min(start_node + BLOCK_LEN) {
-                if !self.is_independent_run(rng, node, self.initial_degree) {
+                if !self.is_independent_run(rng, node, self.initial_degree as usize) {
                     self.upper.fetch_min(node);
                     return;
                 }
@@ -76,7 +119,7 @@ impl RunlengthSampler {
     }
 
     /// In a parallel context, the result is only valid if there's a barrier between sample and result.
-    pub(super) fn result(&self) -> (Node, f64) {
+    pub(crate) fn result(&self) -> (Node, f64) {
         let upper_bound = self.upper.load();
 
         (
@@ -85,7 +128,12 @@ impl RunlengthSampler {
         )
     }
 
-    pub(super) fn continue_with_node(
+    /// Lowers the end of the current epoch to `upper` (e.g. if it was determined elsewhere)
+    pub(crate) fn restrict_upper(&self, upper: Node) {
+        self.upper.fetch_min(upper);
+    }
+
+    pub(crate) fn continue_with_node(
         &self,
         rng: &mut impl Rng,
         node: Node,
@@ -104,12 +152,62 @@ impl RunlengthSampler {
         false
     }
 
-    fn is_independent_run(
+    /// Draws the end of the epoch that starts at `lower` (see [`RunlengthSampler::setup_epoch`]),
+    /// i.e. the first node `> lower` for which the trial of [`RunlengthSampler::continue_with_node`]
+    /// fails, or `upper` if there is none. Rather than one trial per node, we skip sample with a
+    /// geometric distribution whose failure probability bounds the ones of a window of nodes and
+    /// accept a candidate with the ratio of its actual and the bounding probability (thinning).
+    /// The failure probability increases with the node, so the window's last node yields the bound.
+    ///
+    /// The result only depends on the state of `rng` and the parameters of `setup_epoch`; so
+    /// threads with identically seeded random number generators draw the same end.
+    pub(crate) fn sample_epoch_end(
         &self,
         rng: &mut impl Rng,
-        node: usize,
         sampling_attempts: usize,
-    ) -> bool {
+        upper: Node,
+    ) -> Node {
+        let mut node = self.real_lower.load() + 1;
+
+        while node < upper {
+            // the window covers about one expected run length, so the bound remains tight
+            let prob_first = self.probability_is_dependent(node, sampling_attempts);
+            let window_len = (1.0 / prob_first).ceil().min(upper as f64) as Node;
+            let window_end = node.saturating_add(window_len.max(1)).min(upper);
+
+            let prob_bound = self.probability_is_dependent(window_end - 1, sampling_attempts);
+            if prob_bound <= 0.0 {
+                node = window_end;
+                continue;
+            }
+
+            let skip = Geometric::new(prob_bound).unwrap().sample(rng);
+            if skip >= (window_end - node) as u64 {
+                // the geometric distribution is memoryless, so we may restart at the window end
+                node = window_end;
+                continue;
+            }
+
+            let candidate = node + skip as Node;
+            let prob_candidate = self.probability_is_dependent(candidate, sampling_attempts);
+            if rng.gen_bool((prob_candidate / prob_bound).min(1.0)) {
+                return candidate;
+            }
+
+            node = candidate + 1;
+        }
+
+        upper
+    }
+
+    /// Returns `1 - probability_is_independent(node)^sampling_attempts` without cancellation
+    fn probability_is_dependent(&self, node: Node, sampling_attempts: usize) -> f64 {
+        let (total_weight, upper_bound) = self.total_weight_and_upper_bound_for(node);
+        let log_independent = -((upper_bound - total_weight) / total_weight).ln_1p();
+        -(sampling_attempts as f64 * log_independent).exp_m1()
+    }
+
+    fn is_independent_run(&self, rng: &mut impl Rng, node: Node, sampling_attempts: usize) -> bool {
         let prob_single_is_independent = self.probability_is_independent(node);
         let prob_all_independent = prob_single_is_independent.powi(sampling_attempts as i32);
         rng.gen_bool(prob_all_independent)
@@ -120,28 +218,181 @@ impl RunlengthSampler {
         total_weight / upper_bound
     }
 
-    pub(super) fn total_weight_and_upper_bound_for(&self, node: Node) -> (f64, f64) {
+    pub(crate) fn total_weight_and_upper_bound_for(&self, node: Node) -> (f64, f64) {
         let nodes_in_epoch = node - self.real_lower.load();
         let hosts_in_epoch = nodes_in_epoch * self.initial_degree;
 
         let total_weight = self.total_weight.load(Ordering::Relaxed);
 
-        let upper_bound_weight_increase = match self.weight_function.regime() {
-            Regime::Sublinear => {
-                self.weight_initial_degree * nodes_in_epoch as f64 + hosts_in_epoch as f64
-                // TODO: hosts_in_epoc is crude; may use min-degree
+        // each new node contributes its own weight and increases the weights of its hosts
+        let upper_bound_host_increase = match self.properties.curvature {
+            Curvature::Concave | Curvature::Linear => {
+                self.weight_increase_per_host.load(Ordering::Relaxed) * hosts_in_epoch as f64
             }
-            Regime::Superlinear => {
+            Curvature::Convex => {
+                // the increments grow with the degree; as each new node links to a host at most
+                // once, the worst case are `initial_degree` hosts of maximum degree linked by all
                 let ub_dmax = self.max_degree.load() + nodes_in_epoch;
                 let weight_ub_dmax = self.weight_function.get(ub_dmax);
 
-                self.weight_initial_degree * nodes_in_epoch as f64
-                    + (weight_ub_dmax - self.weight_max_degree.load(Ordering::Acquire))
-                        * self.initial_degree as f64
+                (weight_ub_dmax - self.weight_max_degree.load(Ordering::Acquire))
+                    * self.initial_degree as f64
             }
-            Regime::Linear => 2.0 * hosts_in_epoch as f64,
         };
 
+        let upper_bound_weight_increase =
+            self.weight_initial_degree * nodes_in_epoch as f64 + upper_bound_host_increase;
+
         (total_weight, total_weight + upper_bound_weight_increase)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use itertools::Itertools;
+    use pcg_rand::Pcg64;
+    use rand::SeedableRng;
+
+    const LOWER: Node = 1000;
+    const UPPER: Node = 1_000_000;
+    const INITIAL_DEGREE: Node = 3;
+
+    fn sampler(exponent: f64) -> RunlengthSampler {
+        let weight_function = WeightFunction::new(exponent, 0.0);
+        let sampler = RunlengthSampler::new(weight_function.clone(), INITIAL_DEGREE).unwrap();
+        // roughly the state after LOWER nodes with a moderately high maximum degree
+        let total_weight = LOWER as f64 * weight_function.get(2 * INITIAL_DEGREE);
+        sampler.setup_epoch(LOWER, UPPER, 50, total_weight);
+        sampler
+    }
+
+    /// Reference implementation with one Bernoulli trial per node
+    fn bernoulli_epoch_end(sampler: &RunlengthSampler, rng: &mut impl Rng) -> Node {
+        (LOWER + 1..UPPER)
+            .find(|&node| !sampler.is_independent_run(rng, node, INITIAL_DEGREE as usize))
+            .unwrap_or(UPPER)
+    }
+
+    #[test]
+    fn epoch_end_matches_bernoulli_trials() {
+        const SAMPLES: usize = 20000;
+
+        for exponent in [0.5, 1.0, 1.5] {
+            let sampler = sampler(exponent);
+            let mut rng = Pcg64::seed_from_u64(1234);
+
+            let mut geometric = (0..SAMPLES)
+                .map(|_| sampler.sample_epoch_end(&mut rng, INITIAL_DEGREE as usize, UPPER))
+                .collect_vec();
+            let mut bernoulli = (0..SAMPLES)
+                .map(|_| bernoulli_epoch_end(&sampler, &mut rng))
+                .collect_vec();
+
+            assert!(geometric.iter().all(|&end| LOWER < end && end <= UPPER));
+
+            // two-sample Kolmogorov-Smirnov test; the threshold corresponds to a p-value of 1e-3
+            geometric.sort_unstable();
+            bernoulli.sort_unstable();
+
+            let max_cdf_distance = geometric
+                .iter()
+                .chain(bernoulli.iter())
+                .map(|&x| {
+                    let cdf_geometric = geometric.partition_point(|&y| y <= x);
+                    let cdf_bernoulli = bernoulli.partition_point(|&y| y <= x);
+                    cdf_geometric.abs_diff(cdf_bernoulli) as f64 / SAMPLES as f64
+                })
+                .fold(0.0, f64::max);
+
+            let threshold = 1.95 * (2.0 / SAMPLES as f64).sqrt();
+            assert!(
+                max_cdf_distance < threshold,
+                "exponent {}: distance {} exceeds {}",
+                exponent,
+                max_cdf_distance,
+                threshold
+            );
+        }
+    }
+
+    #[test]
+    fn sublinear_bound_holds() {
+        let mut rng = Pcg64::seed_from_u64(4321);
+
+        for (exponent, offset) in [(0.1, 0.0), (0.5, 0.0), (0.5, 2.0), (0.9, 0.5)] {
+            for min_seed_degree in [0, 1, 2, 5] {
+                let weight_function = WeightFunction::new(exponent, offset);
+                let min_degree = min_seed_degree.min(INITIAL_DEGREE);
+
+                // existing nodes; the first ones have the minimum degree (worst case for the bound)
+                let mut degrees = (0..LOWER)
+                    .map(|u| {
+                        if u < 100 {
+                            min_degree
+                        } else {
+                            rng.gen_range(min_degree..min_degree + 20)
+                        }
+                    })
+                    .collect_vec();
+                let total_weight: f64 = degrees.iter().map(|&d| weight_function.get(d)).sum();
+
+                let sampler =
+                    RunlengthSampler::new(weight_function.clone(), INITIAL_DEGREE).unwrap();
+                sampler.set_min_seed_degree(min_seed_degree);
+                sampler.setup_epoch(LOWER, UPPER, 25, total_weight);
+
+                let mut weight = total_weight;
+                for node in LOWER + 1..LOWER + 200 {
+                    weight += weight_function.get(INITIAL_DEGREE);
+                    for _ in 0..INITIAL_DEGREE {
+                        let host = if rng.gen_bool(0.5) {
+                            rng.gen_range(0..100)
+                        } else {
+                            rng.gen_range(0..LOWER)
+                        } as usize;
+
+                        weight += weight_function.get(degrees[host] + 1)
+                            - weight_function.get(degrees[host]);
+                        degrees[host] += 1;
+                    }
+
+                    let (_, upper_bound) = sampler.total_weight_and_upper_bound_for(node);
+                    assert!(
+                        weight <= upper_bound * (1.0 + 1e-12),
+                        "exponent {} offset {} min seed degree {}: weight {} exceeds {}",
+                        exponent,
+                        offset,
+                        min_seed_degree,
+                        weight,
+                        upper_bound
+                    );
+
+                    // never worse than the bound of one per host
+                    let crude_bound = total_weight
+                        + (node - LOWER) as f64
+                            * (weight_function.get(INITIAL_DEGREE) + INITIAL_DEGREE as f64);
+                    assert!(upper_bound <= crude_bound * (1.0 + 1e-12));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_decreasing_kernels() {
+        assert!(RunlengthSampler::new(WeightFunction::new(-0.5, 1.0), INITIAL_DEGREE).is_err());
+        assert!(RunlengthSampler::new(WeightFunction::new(0.0, 1.0), INITIAL_DEGREE).is_ok());
+    }
+
+    #[test]
+    fn linear_bound_includes_offset() {
+        let weight_function = WeightFunction::new(1.0, 5.0);
+        let sampler = RunlengthSampler::new(weight_function, INITIAL_DEGREE).unwrap();
+        sampler.setup_epoch(LOWER, UPPER, 50, 1e4);
+
+        // all hosts have the minimum degree zero, so each edge increases the weight by one
+        let (total_weight, upper_bound) = sampler.total_weight_and_upper_bound_for(LOWER + 10);
+        let weight_increase = 10.0 * (INITIAL_DEGREE as f64 + 5.0) + 10.0 * INITIAL_DEGREE as f64;
+        assert!(upper_bound - total_weight >= weight_increase);
+    }
+}
@@ -0,0 +1,189 @@
+use super::*;
+use crossbeam::atomic::AtomicCell;
+use std::intrinsics::likely;
+use std::ops::Index;
+use std::sync::atomic::AtomicPtr;
+
+const MAX_SEGMENTS: usize = usize::BITS as usize;
+
+/// A lock-free growable array of atomic nodes. It consists of segments of doubling lengths,
+/// i.e. segment `k` has `first_segment_len << k` elements; hence the elements never move and
+/// references to them remain valid while other threads grow the array.
+///
+/// Segments are allocated on demand by [`SegmentedArray::ensure_len`] and initialized with the
+/// fill value. If several threads race to allocate the same segment, all but one discard their
+/// allocation. Accessing an element requires that its segment was allocated before, which
+/// is guaranteed if a call to `ensure_len` happened-before (e.g. by a barrier or by the same
+/// thread).
+pub(super) struct SegmentedArray {
+    segments: [AtomicPtr<AtomicCell<Node>>; MAX_SEGMENTS],
+    log_first_segment_len: u32,
+    fill: Node,
+}
+
+impl SegmentedArray {
+    /// Allocates the first segment with the largest power of two not exceeding `capacity`; it is
+    /// initialized by `num_threads` threads (see [`parallel_init`](super::parallel_init))
+    pub(super) fn new(capacity: usize, fill: Node, num_threads: usize) -> Self {
+        let log_first_segment_len = capacity.max(1).ilog2();
+
+        let array = Self {
+            segments: std::array::from_fn(|_| AtomicPtr::default()),
+            log_first_segment_len,
+            fill,
+        };
+        array.allocate_segment(0, num_threads);
+        array
+    }
+
+    /// Ensures that the elements with indices `0..len` are allocated
+    #[inline]
+    pub(super) fn ensure_len(&self, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        let (last_segment, _) = self.locate(len - 1);
+        if likely(
+            !self.segments[last_segment]
+                .load(Ordering::Acquire)
+                .is_null(),
+        ) {
+            return;
+        }
+
+        for segment in 0..=last_segment {
+            self.allocate_segment(segment, 1);
+        }
+    }
+
+    /// Number of elements in all allocated segments
+    #[allow(dead_code)]
+    pub(super) fn capacity(&self) -> usize {
+        let num_segments = self
+            .segments
+            .iter()
+            .take_while(|s| !s.load(Ordering::Acquire).is_null())
+            .count();
+
+        ((1 << num_segments) - 1) << self.log_first_segment_len
+    }
+
+    /// # Safety
+    /// The segment containing `index` has to be allocated (see [`SegmentedArray::ensure_len`])
+    #[inline]
+    pub(super) unsafe fn get_unchecked(&self, index: usize) -> &AtomicCell<Node> {
+        let (segment, offset) = self.locate(index);
+        let ptr = self.segments.get_unchecked(segment).load(Ordering::Acquire);
+        debug_assert!(!ptr.is_null());
+        &*ptr.add(offset)
+    }
+
+    #[inline]
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let shifted = index + (1 << self.log_first_segment_len);
+        let segment = (shifted.ilog2() - self.log_first_segment_len) as usize;
+        (segment, shifted - self.segment_len(segment))
+    }
+
+    #[inline]
+    fn segment_len(&self, segment: usize) -> usize {
+        1 << (self.log_first_segment_len as usize + segment)
+    }
+
+    fn allocate_segment(&self, segment: usize, num_threads: usize) {
+        if !self.segments[segment].load(Ordering::Acquire).is_null() {
+            return;
+        }
+
+        let len = self.segment_len(segment);
+        let elements: Box<[AtomicCell<Node>]> =
+            parallel_init::filled(len, num_threads, || AtomicCell::new(self.fill))
+                .into_boxed_slice();
+        let ptr = Box::into_raw(elements) as *mut AtomicCell<Node>;
+
+        if self.segments[segment]
+            .compare_exchange(
+                std::ptr::null_mut(),
+                ptr,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // another thread was faster; safety: ptr was obtained from a boxed slice of len
+            drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)) });
+        }
+    }
+}
+
+impl Index<usize> for SegmentedArray {
+    type Output = AtomicCell<Node>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        let (segment, _) = self.locate(index);
+        assert!(!self.segments[segment].load(Ordering::Acquire).is_null());
+        unsafe { self.get_unchecked(index) }
+    }
+}
+
+impl Drop for SegmentedArray {
+    fn drop(&mut self) {
+        for (segment, ptr) in self.segments.iter().enumerate() {
+            let ptr = ptr.load(Ordering::Acquire);
+            if !ptr.is_null() {
+                let len = self.segment_len(segment);
+                // safety: ptr was obtained from a boxed slice of len
+                drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)) });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn locate() {
+        let array = SegmentedArray::new(6, 0, 1);
+        assert_eq!(array.capacity(), 4);
+
+        let expected = [(0, 0), (0, 3), (1, 0), (1, 7), (2, 0), (2, 15), (3, 0)];
+        for (index, (segment, offset)) in [0, 3, 4, 11, 12, 27, 28].into_iter().zip(expected) {
+            assert_eq!(array.locate(index), (segment, offset));
+        }
+    }
+
+    #[test]
+    fn concurrent_growth() {
+        const NUM_THREADS: usize = 4;
+        const PER_THREAD: usize = 10000;
+
+        let array = Arc::new(SegmentedArray::new(1, Node::MAX, 1));
+        let next = Arc::new(AtomicCell::new(0usize));
+
+        let handles = (0..NUM_THREADS)
+            .map(|_| {
+                let array = array.clone();
+                let next = next.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..PER_THREAD {
+                        let index = next.fetch_add(1);
+                        array.ensure_len(index + 1);
+                        array[index].store(index as Node);
+                    }
+                })
+            })
+            .collect_vec();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let len = NUM_THREADS * PER_THREAD;
+        assert!(array.capacity() >= len);
+        assert!((0..len).all(|i| array[i].load() == i as Node));
+    }
+}
@@ -1,8 +1,10 @@
 #![allow(dead_code)]
 
 use super::*;
+use crate::algorithm::algo_parallel_poly_pa::dependent_node::Gains;
 use crate::algorithm::algo_parallel_poly_pa::run_length::RunlengthSampler;
 use crossbeam::atomic::AtomicCell;
+use std::sync::Mutex;
 
 pub(super) struct NodeInfo {
     pub(super) degree: AtomicCell<Node>,
@@ -37,42 +39,60 @@ pub(super) struct State {
     pub(super) runlength_sampler: RunlengthSampler,
 
     pub(super) wmax: AtomicF64,
-    pub(super) max_degree: AtomicCell<usize>,
+    pub(super) max_degree: AtomicCell<Node>,
+
+    /// Offset (relative to the epoch's first node) of the next chunk to be claimed in phase 1;
+    /// reset by the leader in phase 2
+    pub(super) next_unclaimed_node: AtomicCell<Node>,
+
+    /// Per worker, the weight its updates added to the nodes in the previous epoch; written in
+    /// phase 2 and read in phase 1 by the thread sampling the epoch's first node
+    pub(super) gains: Vec<Mutex<Gains>>,
 }
 
 impl State {
     pub(super) fn sequential_set_degree(&self, node: Node, degree: Node) {
-        let info = &self.nodes[node];
-        let old_degree = info.degree.swap(degree);
+        let info = &self.nodes[node as usize];
+        info.degree.store(degree);
 
         self.max_degree.fetch_max(degree);
 
-        let old_weight = self.weight_function.get(old_degree);
+        // nodes not inserted yet have weight zero rather than the weight of degree zero
         let new_weight = self.weight_function.get(degree);
-
-        info.weight.fetch_max(new_weight, Ordering::AcqRel);
+        let old_weight = info.weight.fetch_max(new_weight, Ordering::AcqRel);
 
         self.total_weight
             .fetch_add(new_weight - old_weight, Ordering::AcqRel);
     }
 
     pub(super) fn sequential_increase_degree(&self, node: Node) {
-        self.sequential_set_degree(node, self.nodes[node].degree.load() + 1);
+        self.sequential_set_degree(node, self.nodes[node as usize].degree.load() + 1);
     }
 
-    pub(super) fn sequential_update_node_counts_in_proposal_list(&self, node: Node) {
-        let info = &self.nodes[node];
-        let target_count = ((self.num_seed_nodes as f64) * info.weight.load(Ordering::Relaxed)
+    pub(super) fn sequential_update_node_counts_in_proposal_list(
+        &self,
+        node: Node,
+        num_nodes: Node,
+    ) {
+        let excess = self.update_node_counts_in_proposal_list(node, num_nodes);
+        self.wmax.fetch_max(excess, Ordering::AcqRel);
+    }
+
+    /// Pushes `node` into the proposal list until its count matches its share of the total
+    /// weight among the first `num_nodes` nodes and returns its excess, i.e. its weight per
+    /// entry; `wmax` is left to the caller. May run concurrently for distinct nodes.
+    pub(super) fn update_node_counts_in_proposal_list(&self, node: Node, num_nodes: Node) -> f64 {
+        let info = &self.nodes[node as usize];
+        let target_count = ((num_nodes as f64) * info.weight.load(Ordering::Relaxed)
             / self.total_weight.load(Ordering::Acquire))
-        .ceil() as usize;
+        .ceil() as Node;
 
         if info.count.load() < target_count {
             self.proposal_list
-                .unbuffered_push(node, target_count - info.count.load());
+                .unbuffered_push(node, (target_count - info.count.load()) as usize);
             info.count.store(target_count);
         }
 
-        let excess = info.weight.load(Ordering::Relaxed) / (info.count.load() as f64);
-        self.wmax.fetch_max(excess, Ordering::AcqRel);
+        info.weight.load(Ordering::Relaxed) / (info.count.load() as f64)
     }
 }
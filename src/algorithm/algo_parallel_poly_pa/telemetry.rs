@@ -0,0 +1,189 @@
+//! Per-epoch measurements of the workers for tuning the parallel algorithm. The worker reports
+//! the boundaries of its phases and barriers to a [`Telemetry`]; with [`NoTelemetry`] (the
+//! default) all calls are empty and compiled away.
+
+use super::*;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+pub trait Telemetry: Send + 'static {
+    fn new(rank: usize) -> Self;
+
+    /// Starts the record of an epoch and its phase 1
+    fn begin_epoch(&mut self, epoch_id: usize, epoch_nodes: Range<Node>);
+
+    /// Starts phase 2 after the barrier following phase 1
+    fn begin_phase2(&mut self);
+
+    fn begin_barrier(&mut self);
+    fn end_barrier(&mut self);
+
+    /// `samples` proposals were drawn to link `accepted` hosts
+    fn count_samples(&mut self, samples: u64, accepted: u64);
+
+    fn end_epoch(&mut self, proposal_list_end: usize, wmax: f64);
+
+    fn into_records(self) -> Vec<EpochRecord>;
+}
+
+/// Records nothing
+pub struct NoTelemetry;
+
+impl Telemetry for NoTelemetry {
+    #[inline(always)]
+    fn new(_rank: usize) -> Self {
+        NoTelemetry
+    }
+
+    #[inline(always)]
+    fn begin_epoch(&mut self, _epoch_id: usize, _epoch_nodes: Range<Node>) {}
+
+    #[inline(always)]
+    fn begin_phase2(&mut self) {}
+
+    #[inline(always)]
+    fn begin_barrier(&mut self) {}
+
+    #[inline(always)]
+    fn end_barrier(&mut self) {}
+
+    #[inline(always)]
+    fn count_samples(&mut self, _samples: u64, _accepted: u64) {}
+
+    #[inline(always)]
+    fn end_epoch(&mut self, _proposal_list_end: usize, _wmax: f64) {}
+
+    fn into_records(self) -> Vec<EpochRecord> {
+        Vec::new()
+    }
+}
+
+/// Measures of one worker in one epoch
+#[derive(Clone, Debug, Default)]
+pub struct EpochRecord {
+    epoch_id: usize,
+    rank: usize,
+    epoch_nodes: Range<Node>,
+    phase1: Duration,
+    phase2: Duration,
+    barrier_wait: Duration,
+    samples: u64,
+    rejections: u64,
+    proposal_list_end: usize,
+    wmax: f64,
+}
+
+/// Keeps one [`EpochRecord`] per epoch in memory; time spent at barriers is not attributed to
+/// the phases
+pub struct EpochTelemetry {
+    rank: usize,
+    records: Vec<EpochRecord>,
+    current: EpochRecord,
+    in_phase2: bool,
+    last_lap: Instant,
+}
+
+impl EpochTelemetry {
+    fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        let duration = now.duration_since(self.last_lap);
+        self.last_lap = now;
+        duration
+    }
+
+    fn add_lap_to_phase(&mut self) {
+        let duration = self.lap();
+        if self.in_phase2 {
+            self.current.phase2 += duration;
+        } else {
+            self.current.phase1 += duration;
+        }
+    }
+}
+
+impl Telemetry for EpochTelemetry {
+    fn new(rank: usize) -> Self {
+        Self {
+            rank,
+            records: Vec::new(),
+            current: EpochRecord::default(),
+            in_phase2: false,
+            last_lap: Instant::now(),
+        }
+    }
+
+    fn begin_epoch(&mut self, epoch_id: usize, epoch_nodes: Range<Node>) {
+        self.lap();
+        self.in_phase2 = false;
+        self.current = EpochRecord {
+            epoch_id,
+            rank: self.rank,
+            epoch_nodes,
+            ..Default::default()
+        };
+    }
+
+    fn begin_phase2(&mut self) {
+        self.add_lap_to_phase();
+        self.in_phase2 = true;
+    }
+
+    fn begin_barrier(&mut self) {
+        self.add_lap_to_phase();
+    }
+
+    fn end_barrier(&mut self) {
+        let duration = self.lap();
+        self.current.barrier_wait += duration;
+    }
+
+    fn count_samples(&mut self, samples: u64, accepted: u64) {
+        self.current.samples += samples;
+        self.current.rejections += samples - accepted;
+    }
+
+    fn end_epoch(&mut self, proposal_list_end: usize, wmax: f64) {
+        self.add_lap_to_phase();
+        self.current.proposal_list_end = proposal_list_end;
+        self.current.wmax = wmax;
+        self.records.push(self.current.clone());
+    }
+
+    fn into_records(self) -> Vec<EpochRecord> {
+        self.records
+    }
+}
+
+/// Writes the records of all workers as CSV, ordered by epoch and rank
+pub(super) fn write_csv(path: &Path, mut records: Vec<EpochRecord>) -> io::Result<()> {
+    records.sort_by_key(|r| (r.epoch_id, r.rank));
+
+    let mut writer = BufWriter::new(std::fs::File::create(path)?);
+    writeln!(
+        writer,
+        "epoch,rank,begin,end,len,phase1_s,phase2_s,barrier_s,samples,rejections,proposal_list_end,wmax"
+    )?;
+
+    for r in records {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{:.9},{:.9},{:.9},{},{},{},{}",
+            r.epoch_id,
+            r.rank,
+            r.epoch_nodes.start,
+            r.epoch_nodes.end,
+            r.epoch_nodes.len(),
+            r.phase1.as_secs_f64(),
+            r.phase2.as_secs_f64(),
+            r.barrier_wait.as_secs_f64(),
+            r.samples,
+            r.rejections,
+            r.proposal_list_end,
+            r.wmax
+        )?;
+    }
+
+    writer.flush()
+}
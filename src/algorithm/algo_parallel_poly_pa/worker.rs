@@ -1,31 +1,51 @@
-use super::{proposal_list::Writer, *};
+use super::{dependent_node::DependentNode, proposal_list::Writer, *};
+use crate::algorithm::acceptance::{sample_batched, sample_scalar, BATCH_LEN};
 use crate::algorithm::algo_parallel_poly_pa::proposal_list::Sampler;
 use itertools::Itertools;
+use std::convert::Infallible;
 use std::intrinsics::unlikely;
 use std::ops::Range;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::algorithm::algo_parallel_poly_pa::reports::Reporter;
-use hurdles::Barrier;
+use crate::algorithm::algo_parallel_poly_pa::telemetry::{EpochRecord, Telemetry};
+use crate::barrier::Barrier;
 
-pub struct Worker<R: Rng + Send + Sync> {
+/// Number of epochs between two compactions of the proposal list. Between compactions, each
+/// writer leaves at most one partially filled block, whose unused entries the samplers have to
+/// reject; a compaction costs an additional barrier and is done by the leader in time
+/// `O(num_threads * BLOCK_SIZE)`.
+const COMPACTION_INTERVAL: usize = 8;
+
+/// Number of consecutive nodes a thread claims at once in phase 1
+const CLAIM_LEN: Node = 32;
+
+pub struct Worker<R: Rng + Send + Sync, B: Barrier, T: Telemetry> {
     rank: usize,
-    num_threads: usize,
 
     rng: R,
+    /// Seeded identically in all workers, so that they draw the same epoch ends
+    epoch_end_rng: R,
     algo: Arc<State>,
     proposal_writer: Writer,
     proposal_sampler: Sampler,
+    /// Tests proposals in batches (see [`acceptance`](crate::algorithm::acceptance))
+    batched_acceptance: bool,
 
-    barrier: Barrier,
+    barrier: B,
 
     hosts_linked_in_epoch: Vec<Node>,
     new_nodes: Vec<Node>,
 
-    epoch_nodes: Range<usize>,
+    epoch_nodes: Range<Node>,
 
     previous_weight_estimate: f64,
+    /// The epoch's first node ended the previous epoch; it is `None` in the first epoch, since
+    /// the sequential prefix inserts each node exactly
+    dependent: Option<DependentNode>,
+    /// Weight added to nodes by this worker in the current epoch
+    gains: Vec<(Node, f64)>,
     total_weight_at_epoch_begin: f64,
     total_weight: f64,
     max_degree: Node,
@@ -33,20 +53,26 @@ pub struct Worker<R: Rng + Send + Sync> {
 
     epoch_id: usize,
     reporter: Option<Reporter>,
+    telemetry: T,
 }
 
-impl<R: Rng + Send + Sync> Worker<R> {
+impl<R: Rng + Send + Sync, B: Barrier, T: Telemetry> Worker<R, B, T> {
     pub(super) fn new(
-        rng: R,
+        mut rng: R,
+        epoch_end_rng: R,
         algo: Arc<State>,
-        barrier: Barrier,
+        barrier: B,
         rank: usize,
         num_threads: usize,
+        first_node: Node,
+        batched_acceptance: bool,
     ) -> Self {
         let proposal_writer = Writer::new(algo.proposal_list.clone());
-        let proposal_sampler = Sampler::new(algo.proposal_list.clone());
+        let mut proposal_sampler = Sampler::new(algo.proposal_list.clone());
 
-        let epoch_nodes = 0..algo.num_seed_nodes;
+        // the first epoch starts at the end of the previous one
+        let epoch_nodes = 0..first_node;
+        proposal_sampler.update(&mut rng, first_node);
 
         let reporter = if rank == 0 {
             Some(Reporter::new(algo.num_total_nodes))
@@ -57,22 +83,25 @@ impl<R: Rng + Send + Sync> Worker<R> {
         let node_capacity =
             (5.0 * (algo.num_total_nodes as f64).sqrt() / (num_threads as f64)).max(1000.) as usize;
 
-        let host_capacity = node_capacity * algo.initial_degree;
+        let host_capacity = node_capacity * algo.initial_degree as usize;
 
         Self {
             rank,
-            num_threads,
 
             rng,
+            epoch_end_rng,
             algo,
             proposal_writer,
             proposal_sampler,
+            batched_acceptance,
 
             barrier,
             reporter,
+            telemetry: T::new(rank),
 
             new_nodes: Vec::with_capacity(node_capacity),
             hosts_linked_in_epoch: Vec::with_capacity(host_capacity),
+            gains: Vec::with_capacity(node_capacity + host_capacity),
 
             epoch_nodes,
 
@@ -84,20 +113,32 @@ impl<R: Rng + Send + Sync> Worker<R> {
             epoch_id: 0,
 
             previous_weight_estimate: 0.0,
+            dependent: None,
         }
     }
 
     pub fn run(&mut self) {
         loop {
             self.setup_local_state_for_new_epoch();
+            self.telemetry
+                .begin_epoch(self.epoch_id, self.epoch_nodes.clone());
 
             self.phase1_sample_independent_hosts();
 
             ////////////////////////////////////////////////////////////////////////////////////////
-            self.barrier.wait();
+            self.wait_at_barrier();
+            self.telemetry.begin_phase2();
 
-            (self.epoch_nodes.end, self.previous_weight_estimate) =
-                self.algo.runlength_sampler.result(); // end now points to the node with a dependence
+            self.previous_weight_estimate = self
+                .algo
+                .runlength_sampler
+                .total_weight_and_upper_bound_for(self.epoch_nodes.end)
+                .1;
+
+            if self.is_leader_thread() {
+                // no thread claims nodes until the next epoch's phase 1
+                self.algo.next_unclaimed_node.store(0);
+            }
 
             self.phase2_update_proposal_list();
 
@@ -106,10 +147,24 @@ impl<R: Rng + Send + Sync> Worker<R> {
                 Ordering::AcqRel,
             );
 
-            self.proposal_writer.free_unfinished_range();
+            // writers keep their partially filled blocks between compactions, so no holes are lost
+            let compact = self.epoch_id.is_multiple_of(COMPACTION_INTERVAL)
+                || self.epoch_nodes.end >= self.algo.num_total_nodes;
+
+            if compact {
+                self.proposal_writer.free_unfinished_range();
+            }
 
             ////////////////////////////////////////////////////////////////////////////////////////
-            self.barrier.wait();
+            self.wait_at_barrier();
+
+            if compact {
+                if self.is_leader_thread() {
+                    self.algo.proposal_list.compact_unfinished_ranges();
+                }
+
+                self.wait_at_barrier();
+            }
 
             if let Some(reporter) = self.reporter.as_mut() {
                 reporter.update_epoch(self.epoch_id, self.epoch_nodes.clone());
@@ -123,12 +178,18 @@ impl<R: Rng + Send + Sync> Worker<R> {
                 self.algo.total_weight.load(Ordering::Acquire),
             );
 
-            self.proposal_sampler.update_end();
+            self.proposal_sampler
+                .update(&mut self.rng, self.epoch_nodes.end);
 
             if self.is_leader_thread() {
                 self.assert_correct_degree_sum();
             }
 
+            self.telemetry.end_epoch(
+                self.proposal_sampler.end(),
+                self.algo.wmax.load(Ordering::Acquire),
+            );
+
             if self.epoch_nodes.end >= self.algo.num_total_nodes {
                 break;
             }
@@ -139,74 +200,164 @@ impl<R: Rng + Send + Sync> Worker<R> {
         }
     }
 
-    fn phase1_sample_independent_hosts(&mut self) {
-        let start_node = if self.is_leader_thread() {
-            self.sample_dependent_node(self.epoch_nodes.start);
-            self.epoch_nodes.start + self.num_threads
-        } else {
-            self.epoch_nodes.start + self.rank
-        };
+    pub fn into_telemetry_records(self) -> Vec<EpochRecord> {
+        self.telemetry.into_records()
+    }
+
+    #[inline]
+    fn wait_at_barrier(&mut self) {
+        self.telemetry.begin_barrier();
+        self.barrier.wait();
+        self.telemetry.end_barrier();
+    }
 
+    /// Threads claim chunks of `CLAIM_LEN` consecutive nodes until a claimed node lies beyond the
+    /// epoch's end, which all threads drew identically beforehand. Hence every node of the epoch
+    /// is processed by exactly one thread, independently of which thread claimed it. Chunks are
+    /// claimed in increasing order, hence `new_nodes` remains sorted.
+    fn phase1_sample_independent_hosts(&mut self) {
         let mut hosts = std::mem::take(&mut self.hosts_linked_in_epoch);
 
-        for node in (start_node..self.epoch_nodes.end).step_by(self.num_threads) {
-            if !self.algo.runlength_sampler.continue_with_node(
-                &mut self.rng,
-                node,
-                self.algo.initial_degree,
-            ) {
+        loop {
+            let chunk_start =
+                self.epoch_nodes.start + self.algo.next_unclaimed_node.fetch_add(CLAIM_LEN);
+            if chunk_start >= self.epoch_nodes.end {
                 break;
             }
 
-            self.sample_hosts(&mut hosts, self.epoch_nodes.start, self.algo.initial_degree);
-            self.new_nodes.push(node);
+            for node in chunk_start..self.epoch_nodes.end.min(chunk_start + CLAIM_LEN) {
+                self.new_nodes.push(node);
+                let dependent = if node == self.epoch_nodes.start {
+                    self.dependent.take()
+                } else {
+                    None
+                };
+
+                let samples = match dependent {
+                    Some(dependent) => self.sample_dependent_hosts(&dependent, &mut hosts),
+                    None => self.sample_hosts(&mut hosts, self.algo.initial_degree),
+                };
+                self.telemetry
+                    .count_samples(samples, self.algo.initial_degree as u64);
+            }
         }
 
         self.hosts_linked_in_epoch = hosts;
     }
 
-    fn sample_hosts(&mut self, hosts: &mut Vec<Node>, new_node: Node, number: Node) -> u64 {
+    fn sample_hosts(&mut self, hosts: &mut Vec<Node>, number: Node) -> u64 {
         let mut attempts = 0;
         let wmax_scaled = SCALE / self.algo.wmax.load(Ordering::Acquire);
 
         let begin = hosts.len();
 
         for _ in 0..number {
-            hosts.push(loop {
-                attempts += 1;
-                let proposal = self.proposal_sampler.sample(&mut self.rng, new_node);
+            let nodes = &self.algo.nodes;
+            let sampler = &mut self.proposal_sampler;
+            let linked = &hosts[begin..];
 
+            let propose = |rng: &mut R| {
+                let proposal = sampler.sample(rng);
                 unsafe {
-                    std::intrinsics::prefetch_read_data(self.algo.nodes.as_ptr().add(proposal), 1);
+                    std::intrinsics::prefetch_read_data(nodes.as_ptr().add(proposal as usize), 1);
                 }
+                proposal
+            };
 
-                if hosts.as_slice().split_at(begin).1.contains(&proposal) {
-                    continue;
+            let excess = |u: Node| {
+                if linked.contains(&u) {
+                    return 0.0;
                 }
 
-                if self.do_accept_host(proposal, wmax_scaled) {
-                    break proposal;
-                }
-            });
+                let info = unsafe { nodes.get_unchecked(u as usize) };
+                info.weight.load(Ordering::Acquire) / info.count.load() as f64
+            };
+
+            let accepted = if self.batched_acceptance {
+                sample_batched::<_, BATCH_LEN>(&mut self.rng, wmax_scaled, propose, excess)
+            } else {
+                sample_scalar(&mut self.rng, wmax_scaled, propose, excess)
+            };
+
+            attempts += accepted.proposals;
+            hosts.push(accepted.host);
         }
 
         attempts
     }
 
-    fn do_accept_host(&mut self, proposal: Node, wmax_scaled: f64) -> bool {
-        let info = unsafe { self.algo.nodes.get_unchecked(proposal) };
+    /// Samples the hosts of the epoch's first node, which ended the previous epoch (see
+    /// [`DependentNode`]), and returns the number of proposals
+    fn sample_dependent_hosts(&mut self, dependent: &DependentNode, hosts: &mut Vec<Node>) -> u64 {
+        let wmax_scaled = SCALE / self.algo.wmax.load(Ordering::Acquire);
+        let nodes = &self.algo.nodes;
+        let sampler = &mut self.proposal_sampler;
 
-        let weight = info.weight.load(Ordering::Acquire);
-        let excess = weight / info.count.load() as f64;
+        let gains = self
+            .algo
+            .gains
+            .iter()
+            .map(|gains| gains.lock().unwrap())
+            .collect_vec();
+        let gain_offsets = std::iter::once(0.0)
+            .chain(gains.iter().scan(0.0, |sum, gains| {
+                *sum += gains.total();
+                Some(*sum)
+            }))
+            .collect_vec();
 
-        self.rng.gen::<u64>() < (excess * wmax_scaled) as u64
+        let mut attempts = 0;
+        let Ok(()) = dependent.sample_hosts::<_, Infallible>(
+            &mut self.rng,
+            self.algo.initial_degree as usize,
+            true,
+            hosts,
+            |rng| {
+                let propose = |rng: &mut R| sampler.sample(rng);
+                let excess = |u: Node| {
+                    let info = &nodes[u as usize];
+                    info.weight.load(Ordering::Acquire) / info.count.load() as f64
+                };
+
+                let accepted = sample_scalar(rng, wmax_scaled, propose, excess);
+                attempts += accepted.proposals;
+                Ok(accepted.host)
+            },
+            |rng| {
+                let target = rng.gen::<f64>() * gain_offsets[gains.len()];
+                let rank =
+                    (gain_offsets.partition_point(|&o| o <= target) - 1).min(gains.len() - 1);
+                Ok(gains[rank].node_at(target - gain_offsets[rank]))
+            },
+            |u| {
+                let gain: f64 = gains.iter().map(|gains| gains.gain(u)).sum();
+                Ok(1.0 - gain / nodes[u as usize].weight.load(Ordering::Acquire))
+            },
+        );
+
+        attempts
     }
 
     fn setup_local_state_for_new_epoch(&mut self) {
-        self.epoch_nodes = self.epoch_nodes.end..self.algo.num_total_nodes;
+        // the end points to the first node with a dependence on the new epoch
+        let epoch_end = self.algo.runlength_sampler.sample_epoch_end(
+            &mut self.epoch_end_rng,
+            self.algo.initial_degree as usize,
+            self.algo.num_total_nodes,
+        );
+        self.epoch_nodes = self.epoch_nodes.end..epoch_end;
         self.epoch_id += 1;
 
-        self.total_weight_at_epoch_begin = self.algo.total_weight.load(Ordering::Acquire);
+        let weight_now = self.algo.total_weight.load(Ordering::Acquire);
+        self.dependent = (self.previous_weight_estimate > 0.0).then(|| {
+            DependentNode::new(
+                self.total_weight_at_epoch_begin,
+                self.previous_weight_estimate,
+                weight_now,
+            )
+        });
+
+        self.total_weight_at_epoch_begin = weight_now;
         self.total_weight = self.total_weight_at_epoch_begin;
         self.max_degree = self.algo.max_degree.load();
         self.wmax = self.algo.wmax.load(Ordering::Acquire);
@@ -218,47 +369,48 @@ impl<R: Rng + Send + Sync> Worker<R> {
     fn phase2_update_proposal_list(&mut self) {
         let initial_degree = self.algo.initial_degree;
 
-        // discard nodes beyond epoch's end
-        {
-            let num_keep_nodes = self
-                .new_nodes
-                .iter()
-                .filter(|&&u| u < self.epoch_nodes.end)
-                .count();
-
-            self.hosts_linked_in_epoch
-                .truncate(num_keep_nodes * self.algo.initial_degree);
-
-            self.new_nodes.truncate(num_keep_nodes);
-        }
+        debug_assert!(self.new_nodes.iter().all(|&u| u < self.epoch_nodes.end));
 
-        let host_degree_increases = self.hosts_linked_in_epoch.iter().copied().counts();
+        // sorted rather than counted in a hash map, whose random iteration order would make the
+        // proposal list and the weight sums differ between runs with the same seed
+        let mut hosts = std::mem::take(&mut self.hosts_linked_in_epoch);
+        hosts.sort_unstable();
+        let host_degree_increases = hosts
+            .iter()
+            .dedup_with_count()
+            .map(|(c, &u)| (u, c as Node));
 
         let new_nodes = std::mem::take(&mut self.new_nodes);
         let own_degree_increases = new_nodes.iter().map(|&u| (u, initial_degree));
 
         own_degree_increases
-            .chain(host_degree_increases.into_iter())
+            .chain(host_degree_increases)
             .for_each(|(node, deg_inc)| {
                 self.increase_degree_of_node(node, deg_inc, self.epoch_nodes.end as f64)
             });
 
         self.new_nodes = new_nodes;
+        self.hosts_linked_in_epoch = hosts;
 
         self.hosts_linked_in_epoch.clear();
         self.new_nodes.clear();
 
         self.algo.max_degree.fetch_max(self.max_degree);
         self.algo.wmax.fetch_max(self.wmax, Ordering::AcqRel);
+
+        self.algo.gains[self.rank]
+            .lock()
+            .unwrap()
+            .set(&mut self.gains);
     }
 
     fn increase_degree_of_node(
         &mut self,
-        node: usize,
+        node: Node,
         degree_increase: Node,
         assumed_num_nodes: f64,
     ) {
-        let info = &self.algo.nodes[node];
+        let info = &self.algo.nodes[node as usize];
 
         let old_degree = info.degree.fetch_add(degree_increase);
         let new_degree = old_degree + degree_increase;
@@ -274,34 +426,35 @@ impl<R: Rng + Send + Sync> Worker<R> {
         }
 
         self.total_weight += new_weight - old_weight;
+        self.gains.push((node, new_weight - old_weight));
 
-        let count = (assumed_num_nodes * new_weight / self.total_weight).ceil() as usize;
+        let count = (assumed_num_nodes * new_weight / self.total_weight).ceil() as Node;
 
-        if let Ok(old_count) =
-            info.count
-                .fetch_update(|old| if old >= count { None } else { Some(count) })
+        let count = match info
+            .count
+            .fetch_update(|old| if old >= count { None } else { Some(count) })
         {
-            self.proposal_writer.push(node, count - old_count);
-            self.wmax = self.wmax.max(new_weight / count as f64);
-        }
+            Ok(old_count) => {
+                self.proposal_writer
+                    .push(node, (count - old_count) as usize);
+                count
+            }
+            Err(old_count) => old_count,
+        };
+
+        // also if the count suffices, since the excess grew with the weight
+        self.wmax = self.wmax.max(new_weight / count as f64);
     }
 
     fn assert_correct_degree_sum(&self) {
         debug_assert_eq!(
             self.compute_degree_sum(),
-            self.algo.num_seed_nodes
-                + 2 * (self.epoch_nodes.end - self.algo.num_seed_nodes) * self.algo.initial_degree
+            self.algo.num_seed_nodes as usize
+                + 2 * (self.epoch_nodes.end - self.algo.num_seed_nodes) as usize
+                    * self.algo.initial_degree as usize
         );
     }
 
-    fn sample_dependent_node(&mut self, new_node: Node) {
-        self.new_nodes.push(new_node);
-
-        let mut hosts = std::mem::take(&mut self.hosts_linked_in_epoch);
-        self.sample_hosts(&mut hosts, new_node, self.algo.initial_degree);
-        self.hosts_linked_in_epoch = hosts;
-    }
-
     #[inline]
     fn is_leader_thread(&self) -> bool {
         self.rank == 0
@@ -311,7 +464,7 @@ impl<R: Rng + Send + Sync> Worker<R> {
         self.algo
             .nodes
             .iter()
-            .map(|i| i.degree.load())
+            .map(|i| i.degree.load() as usize)
             .sum::<usize>()
     }
 }
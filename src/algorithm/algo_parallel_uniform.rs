@@ -0,0 +1,131 @@
+use super::algo_uniform::sample_uniform_hosts;
+use super::seed_sequence::SeedSequence;
+use super::*;
+
+use crossbeam::atomic::AtomicCell;
+use itertools::Itertools;
+use rand::SeedableRng;
+use std::sync::Arc;
+use std::thread;
+
+/// Parallel version of [`AlgoUniform`](super::algo_uniform::AlgoUniform). Since the host
+/// distribution of a new node does not depend on the current degrees, all new nodes are
+/// independent; each thread processes a contiguous range of them and only the degree
+/// updates are synchronized.
+///
+/// The threads are seeded from one sequence, whose master is printed and can be passed to
+/// `--seed-sequence`; as the degree updates commute, this replays runs with the same number
+/// of threads.
+pub struct AlgoParallelUniform<R: Rng + Send + Sync> {
+    rng: R,
+    /// Master of the seed sequence; drawn from `rng` if not given
+    seed_sequence: Option<u64>,
+    num_threads: usize,
+    num_seed_nodes: Node,
+    num_total_nodes: Node,
+    initial_degree: Node,
+    without_replacement: bool,
+
+    degrees: Arc<Vec<AtomicCell<Node>>>,
+}
+
+impl<R: Rng + Send + Sync + SeedableRng + 'static> Algorithm<R> for AlgoParallelUniform<R> {
+    const IS_PARALLEL: bool = true;
+
+    fn new(
+        rng: R,
+        num_threads: usize,
+        num_seed_nodes: Node,
+        num_rand_nodes: Node,
+        initial_degree: Node,
+        without_replacement: bool,
+        _resample: bool,
+        weight_function: WeightFunction,
+    ) -> Self {
+        assert!(weight_function.is_constant());
+
+        let num_total_nodes = num_seed_nodes + num_rand_nodes;
+        Self {
+            rng,
+            seed_sequence: None,
+            num_threads,
+            num_seed_nodes,
+            num_total_nodes,
+            initial_degree,
+            without_replacement,
+
+            degrees: Arc::new(
+                (0..num_total_nodes)
+                    .into_iter()
+                    .map(|_| AtomicCell::new(0))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn set_seed_graph_degrees(&mut self, degrees: impl Iterator<Item = Node>) {
+        let mut num_input_degrees = 0;
+
+        for (degree, target) in degrees.zip(self.degrees.iter()) {
+            target.store(degree);
+            num_input_degrees += 1;
+        }
+
+        assert_eq!(num_input_degrees, self.num_seed_nodes);
+    }
+
+    fn from_parameters(rng: R, opt: &Parameters) -> Self {
+        let mut algo: Self = new_from_parameters(rng, opt);
+        algo.seed_sequence = opt.seed_sequence;
+        algo
+    }
+
+    fn run(&mut self, _writer: &mut impl EdgeWriter) {
+        let num_rand_nodes = self.num_total_nodes - self.num_seed_nodes;
+        let chunk_size = num_rand_nodes.div_ceil(self.num_threads as Node);
+
+        let mut seeds = SeedSequence::new(self.seed_sequence.unwrap_or_else(|| self.rng.gen()));
+        println!("seed_sequence:{:016x}", seeds.master());
+
+        let handles = (0..self.num_threads as Node)
+            .into_iter()
+            .map(|rank: Node| {
+                let begin = (self.num_seed_nodes + rank * chunk_size).min(self.num_total_nodes);
+                let end = (begin + chunk_size).min(self.num_total_nodes);
+
+                let mut rng = R::from_seed(seeds.next_seed::<R>());
+                let degrees = self.degrees.clone();
+                let initial_degree = self.initial_degree;
+                let without_replacement = self.without_replacement;
+
+                thread::spawn(move || {
+                    let mut hosts = Vec::with_capacity(initial_degree as usize);
+
+                    for new_node in begin..end {
+                        sample_uniform_hosts(
+                            &mut rng,
+                            &mut hosts,
+                            new_node,
+                            initial_degree,
+                            without_replacement,
+                        );
+
+                        for &h in &hosts {
+                            degrees[h as usize].fetch_add(1);
+                        }
+
+                        degrees[new_node as usize].fetch_add(initial_degree);
+                    }
+                })
+            })
+            .collect_vec();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    fn degrees(&self) -> Vec<Node> {
+        self.degrees.iter().map(|d| d.load()).collect()
+    }
+}
@@ -1,28 +1,43 @@
+use super::acceptance::{sample_batched, sample_scalar, Accepted, BATCH_LEN};
+use super::checkpoint::{self, CheckpointReader, CheckpointWriter, Checkpointer};
+use super::node_info::{FullNodeInfo, NodeInfo};
+use super::storage::{InMemory, Storage, StorageArray};
 use super::*;
+use itertools::Itertools;
 use std::cell::Cell;
 
 const SCALE: f64 = 2.0 * (1u64 << 63) as f64;
 
-#[derive(Clone, Copy, Debug)]
-struct NodeInfo {
-    degree: Node,
-    count: Node,
-    weight: f64,
-    excess: f64,
+/// The proposal list is rebuilt once it has `REBUILD_FACTOR` times as many entries per node as
+/// right after the previous rebuild. Since a rebuild takes time linear in the number of nodes,
+/// the reference value is at least `MIN_PROPOSALS_PER_NODE`; then rebuilds take amortized
+/// constant time per entry pushed.
+const REBUILD_FACTOR: f64 = 2.0;
+const MIN_PROPOSALS_PER_NODE: f64 = 0.25;
+
+/// Upper bound on the proposals drawn at once if the storage batches reads
+const MAX_READ_BATCH: usize = 256;
+
+/// Counters of [`AlgoPolyPa`] reported after a run; `proposals_per_node` exceeding
+/// `rebuild_threshold_per_node` triggers a rebuild of the proposal list
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProposalListStatistics {
+    pub proposals_per_node: f64,
+    pub rebuild_threshold_per_node: f64,
+    pub num_rebuilds: usize,
+    /// Number of hosts of the nodes inserted so far
+    pub num_hosts: usize,
+    /// Number of proposals tested for acceptance, excluding those rejected early
+    pub num_proposals_tested: usize,
 }
 
-impl Default for NodeInfo {
-    fn default() -> Self {
-        Self {
-            degree: 0,
-            count: 1,
-            weight: 0.0,
-            excess: 0.0,
-        }
+impl ProposalListStatistics {
+    pub fn acceptance_rate(&self) -> f64 {
+        self.num_hosts as f64 / self.num_proposals_tested as f64
     }
 }
 
-pub struct AlgoPolyPa<R: Rng> {
+pub struct AlgoPolyPa<R: Rng, S: Storage = InMemory, I: NodeInfo = FullNodeInfo> {
     rng: R,
     num_total_nodes: Node,
     num_seed_nodes: Node,
@@ -32,10 +47,12 @@ pub struct AlgoPolyPa<R: Rng> {
     initial_degree: Node,
     without_replacement: bool,
     resample: bool,
+    /// Tests proposals in batches (see [`acceptance`](super::acceptance))
+    batched_acceptance: bool,
     weight_function: WeightFunction,
 
-    nodes: Vec<NodeInfo>,
-    proposal_list: Vec<Node>,
+    nodes: S::Array<I>,
+    proposal_list: S::Array<Node>,
     total_weight: f64,
     wmax: f64,
     wmax_scaled: f64,
@@ -43,9 +60,24 @@ pub struct AlgoPolyPa<R: Rng> {
     num_samples: Cell<usize>,
     num_resampled: Cell<usize>,
     num_samples_to_reject: Cell<usize>,
+    num_rebuilds: usize,
+    rebuild_threshold_per_node: f64,
+    /// Is [`REBUILD_FACTOR`] except in tests
+    rebuild_factor: f64,
+
+    /// First node not yet inserted by [`Algorithm::run`]
+    next_node: Node,
+    /// Hosts of the node before `next_node`, which are resampled if `resample` is set
+    last_hosts: Vec<Node>,
+    /// Proposals for the current node drawn ahead if reads may wait for I/O (see
+    /// [`StorageArray::reads_may_wait`]), to be consumed
+    /// from the back; discarded once the node's hosts are sampled, since they follow the
+    /// distribution before its insertion
+    read_batch: Vec<Node>,
+    checkpointer: Option<Checkpointer<Self>>,
 }
 
-impl<R: Rng> Algorithm<R> for AlgoPolyPa<R> {
+impl<R: Rng, S: Storage, I: NodeInfo> Algorithm<R> for AlgoPolyPa<R, S, I> {
     const IS_PARALLEL: bool = false;
 
     fn new(
@@ -69,10 +101,11 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPa<R> {
             without_replacement,
             weight_function,
             resample,
+            batched_acceptance: false,
 
             total_weight: 0.0,
-            nodes: vec![Default::default(); num_total_nodes],
-            proposal_list: Vec::with_capacity(4 * num_total_nodes / 3),
+            nodes: S::filled("nodes", num_total_nodes as usize, Default::default()),
+            proposal_list: S::with_capacity("proposals", 4 * num_total_nodes as usize / 3),
 
             num_current_nodes: 0,
             wmax: 0.0,
@@ -81,16 +114,30 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPa<R> {
             num_samples: Cell::new(0),
             num_samples_to_reject: Cell::new(0),
             num_resampled: Cell::new(0),
+            num_rebuilds: 0,
+            rebuild_threshold_per_node: 0.0,
+            rebuild_factor: REBUILD_FACTOR,
+
+            next_node: num_seed_nodes,
+            last_hosts: Vec::new(),
+            read_batch: Vec::new(),
+            checkpointer: None,
         }
     }
 
+    fn from_parameters(rng: R, opt: &Parameters) -> Self {
+        let mut algo: Self = new_from_parameters(rng, opt);
+        algo.batched_acceptance = opt.batched_acceptance;
+        algo
+    }
+
     fn set_seed_graph_degrees(&mut self, degrees: impl Iterator<Item = Node>) {
         let mut num_input_degrees = 0;
 
         for (degree, target) in degrees.zip(self.nodes.iter_mut()) {
-            target.degree = degree;
-            target.weight = self.weight_function.get(degree);
-            self.total_weight += target.weight;
+            let weight = self.weight_function.get(degree);
+            target.set_degree(degree, weight);
+            self.total_weight += weight;
 
             num_input_degrees += 1;
         }
@@ -101,18 +148,24 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPa<R> {
         for u in 0..self.num_seed_nodes {
             self.update_node_counts_in_proposal_list(u);
         }
+
+        self.update_rebuild_threshold(self.num_seed_nodes);
     }
 
     fn run(&mut self, writer: &mut impl EdgeWriter) {
-        let mut hosts: Vec<Node> = Vec::with_capacity(self.initial_degree);
-        let mut prev_hosts = Vec::with_capacity(self.initial_degree);
+        let mut hosts: Vec<Node> = std::mem::take(&mut self.last_hosts);
+        hosts.reserve(self.initial_degree as usize);
+        let mut prev_hosts = Vec::with_capacity(self.initial_degree as usize);
 
-        for new_node in self.num_seed_nodes..self.num_total_nodes {
+        for new_node in self.next_node..self.num_total_nodes {
             if self.without_replacement {
                 if self.resample && !hosts.is_empty() {
                     prev_hosts.clear();
                     for &source in &hosts {
-                        prev_hosts.push((source, self.nodes[source].weight));
+                        prev_hosts.push((
+                            source,
+                            self.nodes[source as usize].weight(&self.weight_function),
+                        ));
                     }
                     prev_hosts.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
 
@@ -121,7 +174,7 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPa<R> {
 
                     hosts.clear();
 
-                    while hosts.len() < self.initial_degree {
+                    while hosts.len() < self.initial_degree as usize {
                         let mut random_weight = self.rng.gen_range(0.0..total_weight);
 
                         let new_node = if random_weight < hosts_total_weight {
@@ -148,7 +201,8 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPa<R> {
                             let new_node = self.sample_host(new_node, |u| {
                                 prev_hosts.iter().any(|&(p, _)| p == u) || hosts.contains(&u)
                             });
-                            total_weight -= self.nodes[new_node].weight;
+                            total_weight -=
+                                self.nodes[new_node as usize].weight(&self.weight_function);
                             new_node
                         };
 
@@ -156,20 +210,21 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPa<R> {
                     }
                 } else {
                     hosts.clear();
-                    while hosts.len() < self.initial_degree {
+                    while hosts.len() < self.initial_degree as usize {
                         let new_node = self.sample_host(new_node, |u| hosts.contains(&u));
                         hosts.push(new_node);
                     }
                 }
             } else {
                 hosts.clear();
-                while hosts.len() < self.initial_degree {
+                while hosts.len() < self.initial_degree as usize {
                     let new_node = self.sample_host(new_node, |_| false);
                     hosts.push(new_node);
                 }
             }
 
             self.num_current_nodes = new_node;
+            self.read_batch.clear();
 
             // update neighbors
             for &h in &hosts {
@@ -177,11 +232,23 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPa<R> {
                 writer.add_edge(new_node, h);
             }
 
-            self.set_degree(new_node, self.initial_degree);
+            self.add_node(new_node, self.initial_degree);
+
+            if self.proposal_list.len() as f64
+                > self.rebuild_threshold_per_node * self.num_current_nodes as f64
+            {
+                self.rebuild_proposal_list(new_node + 1);
+            }
+
+            self.next_node = new_node + 1;
+            if let Some(checkpointer) = &self.checkpointer {
+                self.last_hosts.clone_from(&hosts);
+                checkpointer.save_if_due(self, self.next_node, self.num_total_nodes);
+            }
         }
 
         let num_edges_sampled =
-            ((self.num_total_nodes - self.num_seed_nodes) * self.initial_degree) as f64;
+            (self.num_total_nodes - self.num_seed_nodes) as f64 * self.initial_degree as f64;
 
         println!(
             "Proposals per node: {}",
@@ -203,77 +270,429 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPa<R> {
             self.num_samples_to_reject.get() as f64 / num_edges_sampled
         );
 
+        let statistics = self.proposal_list_statistics();
+        println!("Acceptance rate: {}", statistics.acceptance_rate());
+        println!("Rebuilds: {}", statistics.num_rebuilds);
+
         println!("Wmax: {}", self.wmax);
     }
 
+    fn enable_checkpoints(&mut self, config: CheckpointConfig)
+    where
+        R: Serialize,
+    {
+        self.checkpointer = Some(Checkpointer::new(config, Self::save_checkpoint));
+    }
+
+    fn resume_from_checkpoint(&mut self, path: &Path) -> io::Result<()>
+    where
+        R: DeserializeOwned,
+    {
+        let mut reader = CheckpointReader::open(path)?;
+        reader.expect_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::expect_parameters(
+            &mut reader,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+        checkpoint::expect_weight_function(&mut reader, &self.weight_function)?;
+        reader.expect_u64(self.resample as u64, "resampling")?;
+        // batches consume the random numbers in another order than scalar tests
+        reader.expect_u64(self.batched_acceptance as u64, "batched acceptance")?;
+
+        self.next_node = reader.read_node()?;
+        self.num_current_nodes = reader.read_node()?;
+        for info in &mut self.nodes[..self.next_node as usize] {
+            let degree = reader.read_node()?;
+            info.set_degree(degree, self.weight_function.get(degree));
+            info.set_count(reader.read_node()?);
+        }
+
+        self.proposal_list.clear();
+        reader.read_nodes(|u| self.proposal_list.push(u))?;
+        self.last_hosts.clear();
+        reader.read_nodes(|u| self.last_hosts.push(u))?;
+
+        self.total_weight = reader.read_f64()?;
+        self.wmax = reader.read_f64()?;
+        self.wmax_scaled = reader.read_f64()?;
+        self.rebuild_threshold_per_node = reader.read_f64()?;
+        self.num_rebuilds = reader.read_u64()? as usize;
+        self.num_samples.set(reader.read_u64()? as usize);
+        self.num_resampled.set(reader.read_u64()? as usize);
+        self.num_samples_to_reject.set(reader.read_u64()? as usize);
+
+        self.rng = reader.read_rng()?;
+        Ok(())
+    }
+
     fn degrees(&self) -> Vec<Node> {
-        self.nodes.iter().map(|i| i.degree).collect()
+        self.nodes.iter().map(|i| i.degree()).collect()
+    }
+
+    /// Streams the degrees from the node infos, which may reside in external memory
+    fn degree_distribution(&self) -> Vec<(usize, usize)> {
+        degree_distribution(self.nodes.iter().map(|i| i.degree()))
+    }
+
+    fn degree_sum(&self) -> u64 {
+        self.nodes.iter().map(|i| i.degree() as u64).sum()
     }
 }
 
-impl<R: Rng> AlgoPolyPa<R> {
-    fn sample_host(&mut self, new_node: Node, reject_early: impl Fn(Node) -> bool) -> Node {
-        debug_assert!(!self.proposal_list.is_empty());
-        loop {
-            self.num_samples.update(|x| x + 1);
+const CHECKPOINT_TAG: &str = "polypa";
+
+impl<R: Rng, S: Storage, I: NodeInfo> AlgoPolyPa<R, S, I> {
+    pub fn proposal_list_statistics(&self) -> ProposalListStatistics {
+        ProposalListStatistics {
+            proposals_per_node: self.proposal_list.len() as f64 / self.num_current_nodes as f64,
+            rebuild_threshold_per_node: self.rebuild_threshold_per_node,
+            num_rebuilds: self.num_rebuilds,
+            num_hosts: (self.next_node - self.num_seed_nodes) as usize
+                * self.initial_degree as usize,
+            num_proposals_tested: self.num_samples_to_reject.get(),
+        }
+    }
 
-            let index = self.rng.gen_range(0..new_node + self.proposal_list.len());
+    /// Writes the fields in the order read by [`Algorithm::resume_from_checkpoint`]
+    fn save_checkpoint(&self, writer: &mut CheckpointWriter) -> io::Result<()>
+    where
+        R: Serialize,
+    {
+        writer.write_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::write_parameters(
+            writer,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+        checkpoint::write_weight_function(writer, &self.weight_function)?;
+        writer.write_u64(self.resample as u64)?;
+        writer.write_u64(self.batched_acceptance as u64)?;
+
+        writer.write_u64(self.next_node as u64)?;
+        writer.write_u64(self.num_current_nodes as u64)?;
+        for info in &self.nodes[..self.next_node as usize] {
+            writer.write_u64(info.degree() as u64)?;
+            writer.write_u64(info.count() as u64)?;
+        }
+
+        writer.write_nodes(&self.proposal_list)?;
+        writer.write_nodes(&self.last_hosts)?;
+
+        writer.write_f64(self.total_weight)?;
+        writer.write_f64(self.wmax)?;
+        writer.write_f64(self.wmax_scaled)?;
+        writer.write_f64(self.rebuild_threshold_per_node)?;
+        writer.write_u64(self.num_rebuilds as u64)?;
+        writer.write_u64(self.num_samples.get() as u64)?;
+        writer.write_u64(self.num_resampled.get() as u64)?;
+        writer.write_u64(self.num_samples_to_reject.get() as u64)?;
+
+        writer.write_rng(&self.rng)
+    }
 
-            let proposal = if index < new_node {
-                index
+    /// The proposal list may be empty, e.g. right after a rebuild, since every node has an
+    /// implicit entry
+    fn sample_host(&mut self, new_node: Node, reject_early: impl Fn(Node) -> bool) -> Node {
+        let new_node = new_node as usize;
+        let proposal_list = &self.proposal_list;
+        let nodes = &self.nodes;
+        let weight_function = &self.weight_function;
+
+        let excess = |u: Node| {
+            if reject_early(u) {
+                0.0
             } else {
-                self.proposal_list[index - new_node]
+                nodes[u as usize].excess(weight_function)
+            }
+        };
+
+        // a separate closure, so that the common case does not pay for the branch
+        let accepted = if nodes.reads_may_wait() || proposal_list.reads_may_wait() {
+            let batch_len = self.read_batch_len(new_node);
+            let read_batch = &mut self.read_batch;
+
+            let propose = |rng: &mut R| {
+                if read_batch.is_empty() {
+                    draw_read_batch::<R, S, I>(
+                        rng,
+                        new_node,
+                        proposal_list,
+                        nodes,
+                        batch_len,
+                        read_batch,
+                    );
+                }
+                read_batch.pop().unwrap()
             };
 
-            unsafe {
-                std::intrinsics::prefetch_read_data(self.nodes.as_ptr().add(proposal), 1);
-            }
+            accept(
+                &mut self.rng,
+                self.wmax_scaled,
+                self.batched_acceptance,
+                propose,
+                &excess,
+            )
+        } else {
+            let propose = |rng: &mut R| {
+                let index = rng.gen_range(0..new_node + proposal_list.len());
+
+                let proposal = if index < new_node {
+                    index as Node
+                } else {
+                    proposal_list[index - new_node]
+                };
 
-            if reject_early(proposal) {
-                continue;
-            }
+                unsafe {
+                    std::intrinsics::prefetch_read_data(nodes.as_ptr().add(proposal as usize), 1);
+                }
 
-            self.num_samples_to_reject.update(|x| x + 1);
+                proposal
+            };
 
-            let info = self.nodes[proposal];
+            accept(
+                &mut self.rng,
+                self.wmax_scaled,
+                self.batched_acceptance,
+                propose,
+                &excess,
+            )
+        };
 
-            let accept = self.rng.gen::<u64>() < (info.excess * self.wmax_scaled) as u64;
+        self.num_samples.update(|x| x + accepted.proposals as usize);
+        self.num_samples_to_reject
+            .update(|x| x + accepted.tested as usize);
 
-            if accept {
-                break proposal;
-            }
-        }
+        accepted.host
+    }
+
+    /// Estimates the proposals needed for the hosts of `new_node` by the proposals per host so far
+    fn read_batch_len(&self, new_node: usize) -> usize {
+        let hosts = (new_node - self.num_seed_nodes as usize) * self.initial_degree as usize;
+        let per_host = (self.num_samples.get() + 1) as f64 / (hosts + 1) as f64;
+        ((self.initial_degree as f64 * per_host).ceil() as usize).clamp(1, MAX_READ_BATCH)
+    }
+
+    /// Inserts `node`, which previously had no weight, with the given degree
+    fn add_node(&mut self, node: Node, degree: Node) {
+        let weight = self.weight_function.get(degree);
+        self.nodes[node as usize].set_degree(degree, weight);
+        self.total_weight += weight;
+
+        self.update_node_counts_in_proposal_list(node);
     }
 
     fn set_degree(&mut self, node: Node, degree: Node) {
-        let info = &mut self.nodes[node];
-        info.degree = degree;
+        let info = &mut self.nodes[node as usize];
 
-        let weight_before = info.weight;
-        info.weight = self.weight_function.get(degree);
-        self.total_weight += info.weight - weight_before;
+        let weight_before = info.weight(&self.weight_function);
+        let weight = self.weight_function.get(degree);
+        info.set_degree(degree, weight);
+        self.total_weight += weight - weight_before;
 
         self.update_node_counts_in_proposal_list(node);
     }
 
     fn update_node_counts_in_proposal_list(&mut self, node: Node) {
-        let info = &mut self.nodes[node];
+        let info = &mut self.nodes[node as usize];
+        let weight = info.weight(&self.weight_function);
         let target_count =
-            ((self.num_current_nodes as f64) * info.weight / self.total_weight).ceil() as usize;
+            ((self.num_current_nodes as f64) * weight / self.total_weight).ceil() as Node;
 
-        while info.count < target_count {
+        let mut count = info.count();
+        while count < target_count {
             self.proposal_list.push(node);
-            info.count += 1;
+            count += 1;
+        }
+        info.set_count(count);
+
+        let excess = weight / (count as f64);
+        if self.wmax < excess {
+            self.wmax = excess;
+            self.wmax_scaled = SCALE / excess;
         }
+    }
+
+    /// Rebuilds the proposal list of the nodes `0..num_nodes` from scratch. As the total weight
+    /// grows, the counts needed by a node decrease; the list, however, retains the entries pushed
+    /// earlier, which lowers the excess of their nodes and increases the rejection rate.
+    fn rebuild_proposal_list(&mut self, num_nodes: Node) {
+        self.num_rebuilds += 1;
+
+        // also removes the rounding errors accumulated by the incremental updates
+        self.total_weight = self.nodes[..num_nodes as usize]
+            .iter()
+            .map(|i| i.weight(&self.weight_function))
+            .sum();
 
-        info.excess = info.weight / (info.count as f64);
-        if self.wmax < info.excess {
-            self.wmax = info.excess;
-            self.wmax_scaled = SCALE / info.excess;
+        self.proposal_list.clear();
+        self.wmax = 0.0;
+
+        for node in 0..num_nodes {
+            self.nodes[node as usize].set_count(1);
+            self.update_node_counts_in_proposal_list(node);
         }
+
+        self.update_rebuild_threshold(num_nodes);
+    }
+
+    fn update_rebuild_threshold(&mut self, num_nodes: Node) {
+        let proposals_per_node = self.proposal_list.len() as f64 / num_nodes as f64;
+        self.rebuild_threshold_per_node =
+            self.rebuild_factor * proposals_per_node.max(MIN_PROPOSALS_PER_NODE);
     }
 
     fn increase_degree(&mut self, node: Node) {
-        self.set_degree(node, self.nodes[node as usize].degree + 1);
+        self.set_degree(node, self.nodes[node as usize].degree() + 1);
+    }
+}
+
+#[inline]
+fn accept<R: Rng>(
+    rng: &mut R,
+    wmax_scaled: f64,
+    batched_acceptance: bool,
+    propose: impl FnMut(&mut R) -> Node,
+    excess: impl FnMut(Node) -> f64,
+) -> Accepted {
+    if batched_acceptance {
+        sample_batched::<_, BATCH_LEN>(rng, wmax_scaled, propose, excess)
+    } else {
+        sample_scalar(rng, wmax_scaled, propose, excess)
+    }
+}
+
+/// Draws `num` proposals into `batch` in reverse order of drawing. Before reading any of them,
+/// the pages of the proposal list and then those of the node infos they touch are announced in
+/// increasing order, so that their I/O overlaps. The proposals are consumed in the order they
+/// were drawn (i.e., independently of their values), so they remain independent and uniform.
+fn draw_read_batch<R: Rng, S: Storage, I: NodeInfo>(
+    rng: &mut R,
+    new_node: usize,
+    proposal_list: &S::Array<Node>,
+    nodes: &S::Array<I>,
+    num: usize,
+    batch: &mut Vec<Node>,
+) {
+    let indices = (0..num)
+        .map(|_| rng.gen_range(0..new_node + proposal_list.len()))
+        .collect_vec();
+
+    let mut sorted = indices
+        .iter()
+        .filter(|&&i| i >= new_node)
+        .map(|&i| i - new_node)
+        .collect_vec();
+    sorted.sort_unstable();
+    proposal_list.will_need(sorted.iter().copied());
+
+    batch.extend(indices.iter().rev().map(|&index| {
+        if index < new_node {
+            index as Node
+        } else {
+            proposal_list[index - new_node]
+        }
+    }));
+
+    let mut sorted = batch.iter().map(|&u| u as usize).collect_vec();
+    sorted.sort_unstable();
+    nodes.will_need(sorted.into_iter());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::storage::ExternalMemory;
+    use crate::algorithm::test_util::Setup;
+    use crate::edge_writer::EdgeCounter;
+    use pcg_rand::Pcg64;
+
+    fn setup(exponent: f64) -> Setup {
+        Setup {
+            repeats: 200,
+            num_seed_nodes: 4,
+            num_rand_nodes: 2000,
+            initial_degree: 2,
+            exponent,
+        }
+    }
+
+    /// A rebuild factor close to one rebuilds the proposal list frequently
+    #[test]
+    fn frequent_rebuilds_match_dynamic_weighted_index() {
+        for exponent in [0.5, 1.0, 1.5] {
+            let setup = setup(exponent);
+            let mut num_rebuilds = 0;
+
+            setup.assert_matches_dynamic_weighted_index(|seed| {
+                let mut algo = setup.new_algo::<AlgoPolyPa<_>>(seed, 1);
+                algo.rebuild_factor = 1.02;
+                algo.update_rebuild_threshold(setup.num_seed_nodes);
+                algo.run(&mut EdgeCounter::default());
+
+                let statistics = algo.proposal_list_statistics();
+                num_rebuilds += statistics.num_rebuilds;
+                assert!(statistics.proposals_per_node <= statistics.rebuild_threshold_per_node);
+                assert_eq!(
+                    algo.degree_sum(),
+                    (setup.num_seed_nodes + 2 * setup.num_rand_nodes * setup.initial_degree) as u64
+                );
+                algo.degrees()
+            });
+
+            assert!(
+                num_rebuilds as u64 >= 5 * setup.repeats,
+                "{} rebuilds",
+                num_rebuilds
+            );
+        }
+    }
+
+    /// The proposals of a node are drawn in batches if the external memory exceeds the cache
+    #[test]
+    fn read_batches_match_dynamic_weighted_index() {
+        for exponent in [0.5, 1.0, 1.5] {
+            let setup = setup(exponent);
+            setup.assert_matches_dynamic_weighted_index(|seed| {
+                let mut algo = setup.new_algo::<AlgoPolyPa<_, ExternalMemory>>(seed, 1);
+                algo.nodes.assume_uncached();
+                algo.proposal_list.assume_uncached();
+                algo.run(&mut EdgeCounter::default());
+                assert!(algo.read_batch.is_empty());
+                algo.degrees()
+            });
+        }
+    }
+
+    #[test]
+    fn checkpoint_requires_same_acceptance() {
+        let path = std::env::temp_dir().join(format!(
+            "nlpa-checkpoint-test-{}-batched",
+            std::process::id()
+        ));
+
+        let setup = setup(1.0);
+        let mut algo = setup.new_algo::<AlgoPolyPa<Pcg64>>(1, 1);
+        algo.batched_acceptance = true;
+        algo.enable_checkpoints(CheckpointConfig {
+            path: path.clone(),
+            interval: 1000,
+        });
+        algo.run(&mut EdgeCounter::default());
+
+        let mut scalar = setup.new_algo::<AlgoPolyPa<Pcg64>>(1, 1);
+        assert!(scalar.resume_from_checkpoint(&path).is_err());
+
+        let mut batched = setup.new_algo::<AlgoPolyPa<Pcg64>>(1, 1);
+        batched.batched_acceptance = true;
+        batched.resume_from_checkpoint(&path).unwrap();
+        batched.run(&mut EdgeCounter::default());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(algo.degrees(), batched.degrees());
     }
 }
@@ -0,0 +1,486 @@
+//! PolyPA variant for superlinear kernels. In this regime a few hubs attract almost all edges;
+//! in the proposal list they would occupy most entries and their excess would dominate `wmax`,
+//! so the rejection rate of all other nodes grows.
+//!
+//! Hence, the (at most) `HUB_CAPACITY` nodes of the highest weights are kept in a
+//! [`DynamicWeightedIndex`] and sampled exactly; the remaining tail is sampled by rejection from a
+//! proposal list (including one implicit entry per node) as in [`AlgoPolyPa`]. A host is a hub
+//! with probability `W_hubs / W`, and the proposal counts as well as `wmax` only refer to the
+//! tail weight. If a tail node outgrows the lightest hub, both swap roles; entries of hubs remain
+//! in the proposal list and are rejected.
+//!
+//! As in [`AlgoDynamicWeightedIndex`], checkpoints store the hubs rather than the layout of
+//! their index, which is rebuilt when saving and when resuming.
+//!
+//! [`AlgoDynamicWeightedIndex`]: super::algo_dynamic_weighted_index::AlgoDynamicWeightedIndex
+//! [`AlgoPolyPa`]: super::algo_poly_pa::AlgoPolyPa
+
+use super::checkpoint::{self, CheckpointReader, CheckpointWriter, Checkpointer};
+use super::node_info::{FullNodeInfo, NodeInfo};
+use super::*;
+use dynamic_weighted_index::DynamicWeightedIndex;
+use rand::distributions::Distribution;
+
+const SCALE: f64 = 2.0 * (1u64 << 63) as f64;
+
+/// Number of nodes sampled through the dynamic weighted index
+const HUB_CAPACITY: usize = 32;
+
+/// Marks nodes in the tail in `AlgoPolyPaHubs::hub_slots`
+const NO_HUB: u8 = u8::MAX;
+
+pub struct AlgoPolyPaHubs<R: Rng> {
+    rng: R,
+    num_total_nodes: Node,
+    num_seed_nodes: Node,
+
+    num_current_nodes: Node,
+
+    initial_degree: Node,
+    without_replacement: bool,
+    weight_function: WeightFunction,
+
+    nodes: Vec<FullNodeInfo>,
+    hub_slots: Vec<u8>,
+
+    hubs: Vec<Node>,
+    hub_index: DynamicWeightedIndex,
+    lightest_hub_slot: usize,
+    lightest_hub_weight: f64,
+
+    proposal_list: Vec<Node>,
+    tail_weight: f64,
+    wmax: f64,
+    wmax_scaled: f64,
+
+    num_samples: usize,
+    num_hub_samples: usize,
+    num_promotions: usize,
+
+    /// First node not yet inserted by [`Algorithm::run`]
+    next_node: Node,
+    checkpointer: Option<Checkpointer<Self>>,
+}
+
+impl<R: Rng> Algorithm<R> for AlgoPolyPaHubs<R> {
+    const IS_PARALLEL: bool = false;
+
+    fn new(
+        rng: R,
+        num_threads: usize,
+        num_seed_nodes: Node,
+        num_rand_nodes: Node,
+        initial_degree: Node,
+        without_replacement: bool,
+        resample: bool,
+        weight_function: WeightFunction,
+    ) -> Self {
+        assert_eq!(num_threads, 1);
+        assert!(!resample);
+
+        let num_total_nodes = num_seed_nodes + num_rand_nodes;
+        Self {
+            rng,
+            num_total_nodes,
+            num_seed_nodes,
+            num_current_nodes: 0,
+            initial_degree,
+            without_replacement,
+            weight_function,
+
+            nodes: vec![Default::default(); num_total_nodes as usize],
+            hub_slots: vec![NO_HUB; num_total_nodes as usize],
+
+            hubs: Vec::with_capacity(HUB_CAPACITY),
+            hub_index: DynamicWeightedIndex::new(HUB_CAPACITY),
+            lightest_hub_slot: 0,
+            lightest_hub_weight: 0.0,
+
+            proposal_list: Vec::with_capacity(4 * num_total_nodes as usize / 3),
+            tail_weight: 0.0,
+            wmax: 0.0,
+            wmax_scaled: 0.0,
+
+            num_samples: 0,
+            num_hub_samples: 0,
+            num_promotions: 0,
+
+            next_node: num_seed_nodes,
+            checkpointer: None,
+        }
+    }
+
+    fn set_seed_graph_degrees(&mut self, degrees: impl Iterator<Item = Node>) {
+        let mut num_input_degrees = 0;
+
+        for (degree, target) in degrees.zip(self.nodes.iter_mut()) {
+            let weight = self.weight_function.get(degree);
+            target.set_degree(degree, weight);
+            self.tail_weight += weight;
+
+            num_input_degrees += 1;
+        }
+
+        assert_eq!(num_input_degrees, self.num_seed_nodes);
+        self.num_current_nodes = self.num_seed_nodes;
+
+        for u in 0..self.num_seed_nodes {
+            self.update_node_counts_in_proposal_list(u);
+        }
+
+        for u in 0..self.num_seed_nodes {
+            self.promote_if_heavy(u);
+        }
+    }
+
+    fn run(&mut self, writer: &mut impl EdgeWriter) {
+        let mut hosts: Vec<Node> = Vec::with_capacity(self.initial_degree as usize);
+
+        for new_node in self.next_node..self.num_total_nodes {
+            hosts.clear();
+            while hosts.len() < self.initial_degree as usize {
+                let host = if self.without_replacement {
+                    let host = self.sample_host(new_node, |u| hosts.contains(&u));
+
+                    // a dominant hub would be rejected over and over again; its weight is
+                    // restored by the degree update below
+                    let slot = self.hub_slots[host as usize];
+                    if slot != NO_HUB {
+                        self.hub_index.remove_weight(slot as usize);
+                    }
+
+                    host
+                } else {
+                    self.sample_host(new_node, |_| false)
+                };
+                hosts.push(host);
+            }
+
+            self.num_current_nodes = new_node;
+
+            // update neighbors
+            for &h in &hosts {
+                self.increase_degree(h);
+                writer.add_edge(new_node, h);
+            }
+
+            self.add_node(new_node, self.initial_degree);
+
+            self.next_node = new_node + 1;
+            if self.checkpointer.as_ref().is_some_and(|checkpointer| {
+                checkpointer.save_if_due(self, self.next_node, self.num_total_nodes)
+            }) {
+                self.rebuild_hub_index();
+            }
+        }
+
+        let num_edges_sampled =
+            (self.num_total_nodes - self.num_seed_nodes) as f64 * self.initial_degree as f64;
+
+        println!(
+            "Proposals per node: {}",
+            self.proposal_list.len() as f64 / self.num_current_nodes as f64
+        );
+
+        println!(
+            "Hub weight fraction: {}",
+            self.hub_index.total_weight() / (self.hub_index.total_weight() + self.tail_weight)
+        );
+
+        println!(
+            "Hub samples: {}",
+            self.num_hub_samples as f64 / self.num_samples as f64
+        );
+
+        println!(
+            "Samples per host:   {}",
+            self.num_samples as f64 / num_edges_sampled
+        );
+
+        println!("Promotions: {}", self.num_promotions);
+
+        println!("Wmax: {}", self.wmax);
+    }
+
+    fn enable_checkpoints(&mut self, config: CheckpointConfig)
+    where
+        R: Serialize,
+    {
+        self.checkpointer = Some(Checkpointer::new(config, Self::save_checkpoint));
+    }
+
+    fn resume_from_checkpoint(&mut self, path: &Path) -> io::Result<()>
+    where
+        R: DeserializeOwned,
+    {
+        let mut reader = CheckpointReader::open(path)?;
+        reader.expect_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::expect_parameters(
+            &mut reader,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+        checkpoint::expect_weight_function(&mut reader, &self.weight_function)?;
+
+        self.next_node = reader.read_node()?;
+        self.num_current_nodes = reader.read_node()?;
+        for info in &mut self.nodes[..self.next_node as usize] {
+            let degree = reader.read_node()?;
+            info.set_degree(degree, self.weight_function.get(degree));
+            info.set_count(reader.read_node()?);
+        }
+
+        self.proposal_list.clear();
+        reader.read_nodes(|u| self.proposal_list.push(u))?;
+
+        self.hubs.clear();
+        reader.read_nodes(|u| self.hubs.push(u))?;
+        for (slot, &hub) in self.hubs.iter().enumerate() {
+            self.hub_slots[hub as usize] = slot as u8;
+        }
+        self.rebuild_hub_index();
+        self.lightest_hub_slot = reader.read_u64()? as usize;
+        self.lightest_hub_weight = reader.read_f64()?;
+
+        self.tail_weight = reader.read_f64()?;
+        self.wmax = reader.read_f64()?;
+        self.wmax_scaled = reader.read_f64()?;
+        self.num_samples = reader.read_u64()? as usize;
+        self.num_hub_samples = reader.read_u64()? as usize;
+        self.num_promotions = reader.read_u64()? as usize;
+
+        self.rng = reader.read_rng()?;
+        Ok(())
+    }
+
+    fn degrees(&self) -> Vec<Node> {
+        self.nodes.iter().map(|i| i.degree()).collect()
+    }
+}
+
+const CHECKPOINT_TAG: &str = "polypa-hubs";
+
+impl<R: Rng> AlgoPolyPaHubs<R> {
+    /// Writes the fields in the order read by [`Algorithm::resume_from_checkpoint`]; the hub
+    /// slots follow from the order of the hubs
+    fn save_checkpoint(&self, writer: &mut CheckpointWriter) -> io::Result<()>
+    where
+        R: Serialize,
+    {
+        writer.write_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::write_parameters(
+            writer,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+        checkpoint::write_weight_function(writer, &self.weight_function)?;
+
+        writer.write_u64(self.next_node as u64)?;
+        writer.write_u64(self.num_current_nodes as u64)?;
+        for info in &self.nodes[..self.next_node as usize] {
+            writer.write_u64(info.degree() as u64)?;
+            writer.write_u64(info.count() as u64)?;
+        }
+
+        writer.write_nodes(&self.proposal_list)?;
+
+        writer.write_nodes(&self.hubs)?;
+        // recomputing the lightest hub might pick another one of equal weight
+        writer.write_u64(self.lightest_hub_slot as u64)?;
+        writer.write_f64(self.lightest_hub_weight)?;
+
+        writer.write_f64(self.tail_weight)?;
+        writer.write_f64(self.wmax)?;
+        writer.write_f64(self.wmax_scaled)?;
+        writer.write_u64(self.num_samples as u64)?;
+        writer.write_u64(self.num_hub_samples as u64)?;
+        writer.write_u64(self.num_promotions as u64)?;
+
+        writer.write_rng(&self.rng)
+    }
+
+    /// Replaces the hub index by one built from the current hubs
+    fn rebuild_hub_index(&mut self) {
+        self.hub_index = DynamicWeightedIndex::new(HUB_CAPACITY);
+        for (slot, &hub) in self.hubs.iter().enumerate() {
+            let weight = self.nodes[hub as usize].weight(&self.weight_function);
+            self.hub_index.set_weight(slot, weight);
+        }
+    }
+
+    fn sample_host(&mut self, new_node: Node, reject_early: impl Fn(Node) -> bool) -> Node {
+        let hub_weight = self.hub_index.total_weight();
+
+        loop {
+            self.num_samples += 1;
+
+            // if all nodes are hubs, the tail weight is zero up to rounding errors
+            let all_hubs = self.hubs.len() == new_node as usize;
+
+            let proposal = if all_hubs
+                || self.rng.gen_range(0.0..hub_weight + self.tail_weight) < hub_weight
+            {
+                self.num_hub_samples += 1;
+                self.hubs[self.hub_index.sample(&mut self.rng).unwrap()]
+            } else {
+                self.sample_tail(new_node)
+            };
+
+            // restarting with the choice between hubs and tail conditions on the admissible hosts
+            if !reject_early(proposal) {
+                break proposal;
+            }
+        }
+    }
+
+    /// Samples a non-hub node proportionally to its weight
+    fn sample_tail(&mut self, new_node: Node) -> Node {
+        let new_node = new_node as usize;
+        loop {
+            let index = self.rng.gen_range(0..new_node + self.proposal_list.len());
+
+            let proposal = if index < new_node {
+                index as Node
+            } else {
+                self.proposal_list[index - new_node]
+            };
+
+            unsafe {
+                std::intrinsics::prefetch_read_data(self.nodes.as_ptr().add(proposal as usize), 1);
+            }
+
+            // hubs have no weight in the tail
+            if self.hub_slots[proposal as usize] != NO_HUB {
+                continue;
+            }
+
+            let info = &self.nodes[proposal as usize];
+            if self.rng.gen::<u64>()
+                < (info.excess(&self.weight_function) * self.wmax_scaled) as u64
+            {
+                break proposal;
+            }
+        }
+    }
+
+    /// Inserts `node`, which previously had no weight, with the given degree
+    fn add_node(&mut self, node: Node, degree: Node) {
+        let weight = self.weight_function.get(degree);
+        self.nodes[node as usize].set_degree(degree, weight);
+        self.tail_weight += weight;
+
+        self.update_node_counts_in_proposal_list(node);
+        self.promote_if_heavy(node);
+    }
+
+    fn increase_degree(&mut self, node: Node) {
+        let info = &mut self.nodes[node as usize];
+
+        let weight_before = info.weight(&self.weight_function);
+        let degree = info.degree() + 1;
+        let weight = self.weight_function.get(degree);
+        info.set_degree(degree, weight);
+
+        let slot = self.hub_slots[node as usize];
+        if slot != NO_HUB {
+            let slot = slot as usize;
+            self.hub_index.set_weight(slot, weight);
+
+            if slot == self.lightest_hub_slot {
+                self.update_lightest_hub();
+            }
+        } else {
+            self.tail_weight += weight - weight_before;
+            self.update_node_counts_in_proposal_list(node);
+            self.promote_if_heavy(node);
+        }
+    }
+
+    /// Moves the tail node `node` into the hubs, if there is a free slot or it is heavier than
+    /// the lightest hub (which then moves into the tail)
+    fn promote_if_heavy(&mut self, node: Node) {
+        let weight = self.nodes[node as usize].weight(&self.weight_function);
+
+        let slot = if self.hubs.len() < HUB_CAPACITY {
+            self.hubs.push(node);
+            self.hubs.len() - 1
+        } else if weight > self.lightest_hub_weight {
+            let slot = self.lightest_hub_slot;
+            let demoted = std::mem::replace(&mut self.hubs[slot], node);
+
+            self.hub_slots[demoted as usize] = NO_HUB;
+            self.tail_weight += self.nodes[demoted as usize].weight(&self.weight_function);
+            self.update_node_counts_in_proposal_list(demoted);
+
+            slot
+        } else {
+            return;
+        };
+
+        self.num_promotions += 1;
+        self.hub_slots[node as usize] = slot as u8;
+        self.tail_weight -= weight;
+        self.hub_index.set_weight(slot, weight);
+        self.update_lightest_hub();
+    }
+
+    fn update_lightest_hub(&mut self) {
+        (self.lightest_hub_slot, self.lightest_hub_weight) = self
+            .hubs
+            .iter()
+            .map(|&h| self.nodes[h as usize].weight(&self.weight_function))
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .unwrap();
+    }
+
+    fn update_node_counts_in_proposal_list(&mut self, node: Node) {
+        let info = &mut self.nodes[node as usize];
+        let weight = info.weight(&self.weight_function);
+        let target_count =
+            ((self.num_current_nodes as f64) * weight / self.tail_weight).ceil() as Node;
+
+        let mut count = info.count();
+        while count < target_count {
+            self.proposal_list.push(node);
+            count += 1;
+        }
+        info.set_count(count);
+
+        let excess = weight / (count as f64);
+        if self.wmax < excess {
+            self.wmax = excess;
+            self.wmax_scaled = SCALE / excess;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::test_util::Setup;
+    use crate::edge_writer::EdgeCounter;
+
+    #[test]
+    fn statistics_match_dynamic_weighted_index() {
+        for exponent in [1.2, 2.0] {
+            let setup = Setup {
+                repeats: 300,
+                num_seed_nodes: 4,
+                num_rand_nodes: 2000,
+                initial_degree: 2,
+                exponent,
+            };
+
+            setup.assert_matches_dynamic_weighted_index(|seed| {
+                let mut algo = setup.new_algo::<AlgoPolyPaHubs<_>>(seed, 1);
+                algo.run(&mut EdgeCounter::default());
+                algo.degrees()
+            });
+        }
+    }
+}
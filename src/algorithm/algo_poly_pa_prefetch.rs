@@ -1,34 +1,127 @@
+use super::checkpoint::{self, CheckpointReader, CheckpointWriter, Checkpointer};
+use super::node_info::{FullNodeInfo, NodeInfo};
 use super::*;
+use crate::edge_writer::EdgeCounter;
+use rand::SeedableRng;
 use rand_distr::Distribution;
 use ringbuffer::{
     ConstGenericRingBuffer, RingBuffer, RingBufferExt, RingBufferRead, RingBufferWrite,
 };
 use std::cell::Cell;
 use std::intrinsics::prefetch_read_data;
+use std::str::FromStr;
+use std::time::Instant;
 
 const SCALE: f64 = 2.0 * (1u64 << 63) as f64;
 
-#[derive(Clone, Copy, Debug)]
-struct NodeInfo {
-    degree: Node,
-    count: Node,
-    weight: f64,
-    excess: f64,
+/// Number of proposals the sampler draws (and prefetches) ahead unless chosen otherwise
+pub const DEFAULT_PREFETCH_DEPTH: usize = 8;
+
+/// Depths with an instantiation of [`AlgoPolyPaPrefetch`] that can be selected at runtime
+pub const PREFETCH_DEPTHS: [usize; 5] = [2, 4, 8, 16, 32];
+
+/// Calibration runs on at most this many nodes (about two seconds per depth); their node infos
+/// and proposal list exceed common last-level caches, so larger graphs change the latencies to
+/// hide only moderately
+const CALIBRATION_NODES: usize = 1 << 21;
+
+/// Seed of the calibration runs; they draw from a generator of their own, so that the graph of
+/// a calibrated run equals that of a run with the chosen depth fixed
+const CALIBRATION_SEED: u64 = 0x5eed_ca1b;
+
+#[derive(Eq, Clone, Copy, PartialEq, Debug)]
+pub enum PrefetchDepth {
+    Fixed(usize),
+    /// Picks the fastest depth by [`calibrate_prefetch_depth`]
+    Calibrate,
 }
 
-impl Default for NodeInfo {
-    fn default() -> Self {
-        Self {
-            degree: 0,
-            count: 1,
-            weight: 0.0,
-            excess: 0.0,
+impl FromStr for PrefetchDepth {
+    type Err = String;
+
+    /// Accepts `auto` or one of the [`PREFETCH_DEPTHS`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(PrefetchDepth::Calibrate);
+        }
+
+        match s.parse::<usize>() {
+            Ok(depth) if PREFETCH_DEPTHS.contains(&depth) => Ok(PrefetchDepth::Fixed(depth)),
+            _ => Err(format!(
+                "Unknown prefetch depth: {}; supported are auto and {:?}",
+                s, PREFETCH_DEPTHS
+            )),
         }
     }
 }
 
-pub struct AlgoPolyPaPrefetch<R: Rng> {
-    proposal_list: ProposalList<R>,
+/// Times the algorithm with each of the [`PREFETCH_DEPTHS`] on the parameters in `opt`, reduced
+/// to at most [`CALIBRATION_NODES`] nodes, and returns the fastest depth. All depths are seeded
+/// with [`CALIBRATION_SEED`]; the generator of the actual run is left untouched.
+pub fn calibrate_prefetch_depth<R: Rng + SeedableRng, I: NodeInfo>(opt: &Parameters) -> usize {
+    let num_nodes = opt.nodes.min(CALIBRATION_NODES) as Node;
+
+    fastest_depth(|depth| {
+        let seed = CALIBRATION_SEED;
+        let runtime = match depth {
+            2 => time_run::<R, I, 2>(seed, num_nodes, opt),
+            4 => time_run::<R, I, 4>(seed, num_nodes, opt),
+            8 => time_run::<R, I, 8>(seed, num_nodes, opt),
+            16 => time_run::<R, I, 16>(seed, num_nodes, opt),
+            32 => time_run::<R, I, 32>(seed, num_nodes, opt),
+            _ => unreachable!(),
+        };
+        println!(
+            "Calibration of prefetch depth {} on {} nodes: {}s",
+            depth, num_nodes, runtime
+        );
+        runtime
+    })
+}
+
+/// Returns the depth among the [`PREFETCH_DEPTHS`] with the smallest runtime, timing each once
+fn fastest_depth(mut time: impl FnMut(usize) -> f64) -> usize {
+    let runtimes = PREFETCH_DEPTHS.map(&mut time);
+
+    PREFETCH_DEPTHS
+        .into_iter()
+        .zip(runtimes)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+        .0
+}
+
+fn time_run<R: Rng + SeedableRng, I: NodeInfo, const PREFETCH_LEN: usize>(
+    seed: u64,
+    num_nodes: Node,
+    opt: &Parameters,
+) -> f64 {
+    let num_seed_nodes = opt.seed_nodes.unwrap() as Node;
+    let mut algorithm = AlgoPolyPaPrefetch::<R, I, PREFETCH_LEN>::new(
+        R::seed_from_u64(seed),
+        1,
+        num_seed_nodes,
+        num_nodes,
+        opt.initial_degree as Node,
+        opt.without_replacement,
+        false,
+        WeightFunction::new(opt.exponent, opt.offset),
+    );
+    algorithm.set_seed_graph_degrees((0..num_seed_nodes).map(|_| 1));
+
+    let start = Instant::now();
+    algorithm.run(&mut EdgeCounter::default());
+    start.elapsed().as_secs_f64()
+}
+
+/// Samples with a look-ahead of `PREFETCH_LEN` proposals; the best depth depends on the memory
+/// latency of the machine and the size of the graph (see [`calibrate_prefetch_depth`])
+pub struct AlgoPolyPaPrefetch<
+    R: Rng,
+    I: NodeInfo = FullNodeInfo,
+    const PREFETCH_LEN: usize = DEFAULT_PREFETCH_DEPTH,
+> {
+    proposal_list: ProposalList<R, PREFETCH_LEN>,
     num_total_nodes: Node,
     num_seed_nodes: Node,
 
@@ -38,16 +131,22 @@ pub struct AlgoPolyPaPrefetch<R: Rng> {
     without_replacement: bool,
     weight_function: WeightFunction,
 
-    nodes: Vec<NodeInfo>,
+    nodes: Vec<I>,
     total_weight: f64,
     wmax: f64,
     wmax_scaled: f64,
 
     num_samples: Cell<usize>,
     num_samples_to_reject: Cell<usize>,
+
+    /// First node not yet inserted by [`Algorithm::run`]
+    next_node: Node,
+    checkpointer: Option<Checkpointer<Self>>,
 }
 
-impl<R: Rng> Algorithm<R> for AlgoPolyPaPrefetch<R> {
+impl<R: Rng, I: NodeInfo, const PREFETCH_LEN: usize> Algorithm<R>
+    for AlgoPolyPaPrefetch<R, I, PREFETCH_LEN>
+{
     const IS_PARALLEL: bool = false;
 
     fn new(
@@ -72,8 +171,8 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPaPrefetch<R> {
             weight_function,
 
             total_weight: 0.0,
-            nodes: vec![Default::default(); num_total_nodes],
-            proposal_list: ProposalList::new(rng, 11 * num_total_nodes / 10),
+            nodes: vec![Default::default(); num_total_nodes as usize],
+            proposal_list: ProposalList::new(rng, 11 * num_total_nodes as usize / 10),
 
             num_current_nodes: 0.0,
             wmax: 0.0,
@@ -81,6 +180,9 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPaPrefetch<R> {
 
             num_samples: Cell::new(0),
             num_samples_to_reject: Cell::new(0),
+
+            next_node: num_seed_nodes,
+            checkpointer: None,
         }
     }
 
@@ -88,9 +190,9 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPaPrefetch<R> {
         let mut num_input_degrees = 0;
 
         for (degree, target) in degrees.zip(self.nodes.iter_mut()) {
-            target.degree = degree;
-            target.weight = self.weight_function.get(degree);
-            self.total_weight += target.weight;
+            let weight = self.weight_function.get(degree);
+            target.set_degree(degree, weight);
+            self.total_weight += weight;
 
             num_input_degrees += 1;
         }
@@ -101,14 +203,15 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPaPrefetch<R> {
         for u in 0..self.num_seed_nodes {
             self.update_node_counts_in_proposal_list(u);
         }
-        self.proposal_list.set_num_nodes(self.num_seed_nodes);
+        self.proposal_list
+            .set_num_nodes(self.num_seed_nodes as usize);
     }
 
     fn run(&mut self, writer: &mut impl EdgeWriter) {
-        let mut hosts = vec![0; self.initial_degree];
+        let mut hosts = vec![0; self.initial_degree as usize];
         self.proposal_list.prefetch();
 
-        for new_node in self.num_seed_nodes..self.num_total_nodes {
+        for new_node in self.next_node..self.num_total_nodes {
             if self.without_replacement {
                 for i in 0..hosts.len() {
                     hosts[i] = self.sample_host(|u| hosts[0..i].contains(&u));
@@ -127,8 +230,13 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPaPrefetch<R> {
                 writer.add_edge(new_node, h);
             }
 
-            self.set_degree(new_node, self.initial_degree);
-            self.proposal_list.set_num_nodes(new_node + 1);
+            self.add_node(new_node, self.initial_degree);
+            self.proposal_list.set_num_nodes(new_node as usize + 1);
+
+            self.next_node = new_node + 1;
+            if let Some(checkpointer) = &self.checkpointer {
+                checkpointer.save_if_due(self, self.next_node, self.num_total_nodes);
+            }
         }
 
         println!(
@@ -139,13 +247,15 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPaPrefetch<R> {
         println!(
             "Samples per host:   {}",
             self.num_samples.get() as f64
-                / ((self.num_total_nodes - self.num_seed_nodes) * self.initial_degree) as f64
+                / ((self.num_total_nodes - self.num_seed_nodes) as f64
+                    * self.initial_degree as f64)
         );
 
         println!(
             "Samples per host tr: {}",
             self.num_samples_to_reject.get() as f64
-                / ((self.num_total_nodes - self.num_seed_nodes) * self.initial_degree) as f64
+                / ((self.num_total_nodes - self.num_seed_nodes) as f64
+                    * self.initial_degree as f64)
         );
 
         println!("Wmax: {}", self.wmax);
@@ -154,7 +264,7 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPaPrefetch<R> {
             "Wmax-real: {:?}",
             self.nodes
                 .iter()
-                .map(|i| (i.degree, i.weight / i.count as f64))
+                .map(|i| (i.degree(), i.excess(&self.weight_function)))
                 .fold((0, 0.0), |s, x| -> (Node, f64) {
                     if s.1 > x.1 {
                         s
@@ -165,12 +275,86 @@ impl<R: Rng> Algorithm<R> for AlgoPolyPaPrefetch<R> {
         );
     }
 
+    fn enable_checkpoints(&mut self, config: CheckpointConfig)
+    where
+        R: Serialize,
+    {
+        self.checkpointer = Some(Checkpointer::new(config, Self::save_checkpoint));
+    }
+
+    fn resume_from_checkpoint(&mut self, path: &Path) -> io::Result<()>
+    where
+        R: DeserializeOwned,
+    {
+        let mut reader = CheckpointReader::open(path)?;
+        reader.expect_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::expect_parameters(
+            &mut reader,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+        checkpoint::expect_weight_function(&mut reader, &self.weight_function)?;
+        reader.expect_u64(PREFETCH_LEN as u64, "prefetch depth")?;
+
+        self.next_node = reader.read_node()?;
+        self.num_current_nodes = reader.read_f64()?;
+        for info in &mut self.nodes[..self.next_node as usize] {
+            let degree = reader.read_node()?;
+            info.set_degree(degree, self.weight_function.get(degree));
+            info.set_count(reader.read_node()?);
+        }
+
+        self.total_weight = reader.read_f64()?;
+        self.wmax = reader.read_f64()?;
+        self.wmax_scaled = reader.read_f64()?;
+        self.num_samples.set(reader.read_u64()? as usize);
+        self.num_samples_to_reject.set(reader.read_u64()? as usize);
+
+        self.proposal_list.restore(&mut reader)
+    }
+
     fn degrees(&self) -> Vec<Node> {
-        self.nodes.iter().map(|i| i.degree).collect()
+        self.nodes.iter().map(|i| i.degree()).collect()
     }
 }
 
-impl<R: Rng> AlgoPolyPaPrefetch<R> {
+const CHECKPOINT_TAG: &str = "polypa-prefetch";
+
+impl<R: Rng, I: NodeInfo, const PREFETCH_LEN: usize> AlgoPolyPaPrefetch<R, I, PREFETCH_LEN> {
+    /// Writes the fields in the order read by [`Algorithm::resume_from_checkpoint`]
+    fn save_checkpoint(&self, writer: &mut CheckpointWriter) -> io::Result<()>
+    where
+        R: Serialize,
+    {
+        writer.write_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::write_parameters(
+            writer,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+        checkpoint::write_weight_function(writer, &self.weight_function)?;
+        writer.write_u64(PREFETCH_LEN as u64)?;
+
+        writer.write_u64(self.next_node as u64)?;
+        writer.write_f64(self.num_current_nodes)?;
+        for info in &self.nodes[..self.next_node as usize] {
+            writer.write_u64(info.degree() as u64)?;
+            writer.write_u64(info.count() as u64)?;
+        }
+
+        writer.write_f64(self.total_weight)?;
+        writer.write_f64(self.wmax)?;
+        writer.write_f64(self.wmax_scaled)?;
+        writer.write_u64(self.num_samples.get() as u64)?;
+        writer.write_u64(self.num_samples_to_reject.get() as u64)?;
+
+        self.proposal_list.save(writer)
+    }
+
     fn sample_host(&mut self, reject_early: impl Fn(Node) -> bool) -> Node {
         loop {
             self.num_samples.update(|x| x + 1);
@@ -188,10 +372,10 @@ impl<R: Rng> AlgoPolyPaPrefetch<R> {
 
             self.num_samples_to_reject.update(|x| x + 1);
 
-            let info = self.nodes[proposal];
+            let info = self.nodes[proposal as usize];
 
-            let accept =
-                self.proposal_list.rng().gen::<u64>() < (info.excess * self.wmax_scaled) as u64;
+            let accept = self.proposal_list.rng().gen::<u64>()
+                < (info.excess(&self.weight_function) * self.wmax_scaled) as u64;
             //let accept = rng.gen_bool(info.excess / self.wmax);
 
             if accept {
@@ -200,49 +384,57 @@ impl<R: Rng> AlgoPolyPaPrefetch<R> {
         }
     }
 
+    /// Inserts `node`, which previously had no weight, with the given degree
+    fn add_node(&mut self, node: Node, degree: Node) {
+        let weight = self.weight_function.get(degree);
+        self.nodes[node as usize].set_degree(degree, weight);
+        self.total_weight += weight;
+
+        self.update_node_counts_in_proposal_list(node);
+    }
+
     fn set_degree(&mut self, node: Node, degree: Node) {
-        let info = &mut self.nodes[node];
-        info.degree = degree;
+        let info = &mut self.nodes[node as usize];
 
-        let weight_before = info.weight;
-        info.weight = self.weight_function.get(degree);
-        self.total_weight += info.weight - weight_before;
+        let weight_before = info.weight(&self.weight_function);
+        let weight = self.weight_function.get(degree);
+        info.set_degree(degree, weight);
+        self.total_weight += weight - weight_before;
 
         self.update_node_counts_in_proposal_list(node);
     }
 
     fn update_node_counts_in_proposal_list(&mut self, node: Node) {
-        let info = &mut self.nodes[node];
-        let target_count =
-            (self.num_current_nodes * info.weight / self.total_weight).ceil() as usize;
-
-        self.proposal_list
-            .push(node, target_count.saturating_sub(info.count));
-        info.count = target_count;
-
-        info.excess = info.weight / (info.count as f64);
-        if self.wmax < info.excess {
-            self.wmax = info.excess;
-            self.wmax_scaled = SCALE / info.excess;
+        let info = &mut self.nodes[node as usize];
+        let weight = info.weight(&self.weight_function);
+        let target_count = (self.num_current_nodes * weight / self.total_weight).ceil() as Node;
+
+        // the count must not drop below the number of entries (e.g. for decreasing kernels)
+        let count = target_count.max(info.count());
+        self.proposal_list.push(node, count - info.count());
+        info.set_count(count);
+
+        let excess = weight / (count as f64);
+        if self.wmax < excess {
+            self.wmax = excess;
+            self.wmax_scaled = SCALE / excess;
         }
     }
 
     fn increase_degree(&mut self, node: Node) {
-        self.set_degree(node, self.nodes[node as usize].degree + 1);
+        self.set_degree(node, self.nodes[node as usize].degree() + 1);
     }
 }
 
-const PREFETCH_LEN: usize = 8;
-
-struct ProposalList<R: Rng> {
-    proposal_list: Vec<usize>,
+struct ProposalList<R: Rng, const PREFETCH_LEN: usize> {
+    proposal_list: Vec<Node>,
     index_buffer: ConstGenericRingBuffer<usize, PREFETCH_LEN>,
     rng: R,
     num_nodes: usize,
     prefetched_size: usize,
 }
 
-impl<R: Rng> ProposalList<R> {
+impl<R: Rng, const PREFETCH_LEN: usize> ProposalList<R, PREFETCH_LEN> {
     pub fn new(rng: R, capacity: usize) -> Self {
         Self {
             rng,
@@ -253,7 +445,7 @@ impl<R: Rng> ProposalList<R> {
         }
     }
 
-    pub fn push(&mut self, value: usize, count: usize) {
+    pub fn push(&mut self, value: Node, count: Node) {
         for _ in 0..count {
             self.proposal_list.push(value);
         }
@@ -277,6 +469,42 @@ impl<R: Rng> ProposalList<R> {
         &mut self.rng
     }
 
+    /// Writes the list, the prefetched indices (which have consumed random numbers) and the
+    /// generator
+    fn save(&self, writer: &mut CheckpointWriter) -> io::Result<()>
+    where
+        R: Serialize,
+    {
+        writer.write_nodes(&self.proposal_list)?;
+        writer.write_u64(self.num_nodes as u64)?;
+        writer.write_u64(self.prefetched_size as u64)?;
+
+        writer.write_u64(self.index_buffer.len() as u64)?;
+        for &index in self.index_buffer.iter() {
+            writer.write_u64(index as u64)?;
+        }
+
+        writer.write_rng(&self.rng)
+    }
+
+    fn restore(&mut self, reader: &mut CheckpointReader) -> io::Result<()>
+    where
+        R: DeserializeOwned,
+    {
+        self.proposal_list.clear();
+        reader.read_nodes(|u| self.proposal_list.push(u))?;
+        self.num_nodes = reader.read_u64()? as usize;
+        self.prefetched_size = reader.read_u64()? as usize;
+
+        self.index_buffer.clear();
+        for _ in 0..reader.read_u64()? {
+            self.index_buffer.push(reader.read_u64()? as usize);
+        }
+
+        self.rng = reader.read_rng()?;
+        Ok(())
+    }
+
     fn prefetch(&mut self) {
         assert!(self.index_buffer.capacity() > 0);
 
@@ -315,7 +543,7 @@ impl<R: Rng> ProposalList<R> {
         let mut buffer_index = -1;
         loop {
             buffer_index += geom.sample(&mut self.rng) as isize + 1;
-            if buffer_index > self.index_buffer.len() as isize {
+            if buffer_index >= self.index_buffer.len() as isize {
                 break;
             }
 
@@ -338,19 +566,89 @@ impl<R: Rng> ProposalList<R> {
         self.prefetched_size = elements;
     }
 
-    fn sample(&mut self) -> usize {
+    fn sample(&mut self) -> Node {
         let index = self.index_buffer.dequeue().unwrap();
         self.prefetch();
 
-        let read = unsafe {
-            *self
-                .proposal_list
-                .get_unchecked(index.saturating_sub(self.num_nodes))
-        };
+        // the list may still be empty (e.g. for regular seed graphs), so only read beyond the nodes
         if index < self.num_nodes {
-            index
+            index as Node
         } else {
-            read
+            unsafe { *self.proposal_list.get_unchecked(index - self.num_nodes) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pcg_rand::Pcg64;
+
+    #[test]
+    fn parse_prefetch_depths() {
+        assert_eq!("auto".parse(), Ok(PrefetchDepth::Calibrate));
+        assert_eq!("16".parse(), Ok(PrefetchDepth::Fixed(16)));
+        assert!("3".parse::<PrefetchDepth>().is_err());
+        assert!("".parse::<PrefetchDepth>().is_err());
+    }
+
+    #[test]
+    fn calibration_picks_fastest_depth() {
+        for fastest in PREFETCH_DEPTHS {
+            let mut timed = Vec::new();
+            let depth = fastest_depth(|depth| {
+                timed.push(depth);
+                (depth as f64 - fastest as f64).abs()
+            });
+
+            assert_eq!(depth, fastest);
+            assert_eq!(timed, PREFETCH_DEPTHS);
+        }
+    }
+
+    #[test]
+    fn prefetched_proposals_remain_uniform_after_growth() {
+        const TRIALS: usize = 20000;
+        const LEN: usize = 8;
+
+        // each list entry has a distinct value beyond the node ids, so every sample identifies
+        // the index it was drawn from
+        let (nodes_before, entries_before) = (10, 10);
+        let (nodes_after, entries_after) = (15, 25);
+        let elements = nodes_after + entries_after;
+
+        let mut counts = vec![0usize; elements];
+        let mut rng = Pcg64::seed_from_u64(1);
+        for _ in 0..TRIALS {
+            let mut list = ProposalList::<Pcg64, LEN>::new(Pcg64::seed_from_u64(rng.gen()), 0);
+            for i in 0..entries_before {
+                list.push((nodes_after + i) as Node, 1);
+            }
+            list.set_num_nodes(nodes_before);
+            list.prefetch();
+
+            for i in entries_before..entries_after {
+                list.push((nodes_after + i) as Node, 1);
+            }
+            list.set_num_nodes(nodes_after);
+
+            // the first LEN samples are the proposals drawn before the growth
+            for _ in 0..LEN {
+                counts[list.sample() as usize] += 1;
+            }
+        }
+
+        let p = 1.0 / elements as f64;
+        let expected = (TRIALS * LEN) as f64 * p;
+        let stddev = (expected * (1.0 - p)).sqrt();
+        for (index, &count) in counts.iter().enumerate() {
+            assert!(
+                (count as f64 - expected).abs() < 5.0 * stddev,
+                "index {}: {} samples, expected {}",
+                index,
+                count,
+                expected
+            );
         }
     }
 }
@@ -0,0 +1,211 @@
+use super::checkpoint::{self, CheckpointReader, CheckpointWriter, Checkpointer};
+use super::*;
+
+/// Uniform attachment (i.e. a random recursive tree for `initial_degree == 1`). This is the
+/// special case of `exponent == 0`, where all nodes carry the same weight. Hence, the hosts of
+/// a new node are drawn uniformly from all previous nodes and no proposal list or rejection
+/// sampling is needed.
+///
+/// If all weights coincide, resampling the previous hosts yields the same distribution as
+/// sampling without replacement from scratch; so `resample` has no effect.
+pub struct AlgoUniform<R: Rng> {
+    rng: R,
+    num_seed_nodes: Node,
+    num_total_nodes: Node,
+    initial_degree: Node,
+    without_replacement: bool,
+
+    degrees: Vec<Node>,
+    /// First node not yet inserted by [`Algorithm::run`]
+    next_node: Node,
+    checkpointer: Option<Checkpointer<Self>>,
+}
+
+impl<R: Rng> Algorithm<R> for AlgoUniform<R> {
+    const IS_PARALLEL: bool = false;
+
+    fn new(
+        rng: R,
+        num_threads: usize,
+        num_seed_nodes: Node,
+        num_rand_nodes: Node,
+        initial_degree: Node,
+        without_replacement: bool,
+        _resample: bool,
+        weight_function: WeightFunction,
+    ) -> Self {
+        assert_eq!(num_threads, 1);
+        assert!(weight_function.is_constant());
+
+        let num_total_nodes = num_seed_nodes + num_rand_nodes;
+        Self {
+            rng,
+            num_seed_nodes,
+            num_total_nodes,
+            initial_degree,
+            without_replacement,
+
+            degrees: vec![0; num_total_nodes as usize],
+            next_node: num_seed_nodes,
+            checkpointer: None,
+        }
+    }
+
+    fn set_seed_graph_degrees(&mut self, degrees: impl Iterator<Item = Node>) {
+        let mut num_input_degrees = 0;
+
+        for (degree, target) in degrees.zip(self.degrees.iter_mut()) {
+            *target = degree;
+            num_input_degrees += 1;
+        }
+
+        assert_eq!(num_input_degrees, self.num_seed_nodes);
+    }
+
+    fn run(&mut self, writer: &mut impl EdgeWriter) {
+        let mut hosts = Vec::with_capacity(self.initial_degree as usize);
+
+        for new_node in self.next_node..self.num_total_nodes {
+            sample_uniform_hosts(
+                &mut self.rng,
+                &mut hosts,
+                new_node,
+                self.initial_degree,
+                self.without_replacement,
+            );
+
+            for &h in &hosts {
+                self.degrees[h as usize] += 1;
+                writer.add_edge(new_node, h);
+            }
+
+            self.degrees[new_node as usize] = self.initial_degree;
+
+            self.next_node = new_node + 1;
+            if let Some(checkpointer) = &self.checkpointer {
+                checkpointer.save_if_due(self, self.next_node, self.num_total_nodes);
+            }
+        }
+    }
+
+    fn enable_checkpoints(&mut self, config: CheckpointConfig)
+    where
+        R: Serialize,
+    {
+        self.checkpointer = Some(Checkpointer::new(config, Self::save_checkpoint));
+    }
+
+    fn resume_from_checkpoint(&mut self, path: &Path) -> io::Result<()>
+    where
+        R: DeserializeOwned,
+    {
+        let mut reader = CheckpointReader::open(path)?;
+        reader.expect_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::expect_parameters(
+            &mut reader,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+
+        self.next_node = reader.read_node()?;
+        let mut degrees = self.degrees.iter_mut();
+        reader.read_nodes(|degree| *degrees.next().unwrap() = degree)?;
+        self.rng = reader.read_rng()?;
+
+        Ok(())
+    }
+
+    fn degrees(&self) -> Vec<Node> {
+        self.degrees.clone()
+    }
+}
+
+const CHECKPOINT_TAG: &str = "uniform";
+
+impl<R: Rng> AlgoUniform<R> {
+    fn save_checkpoint(&self, writer: &mut CheckpointWriter) -> io::Result<()>
+    where
+        R: Serialize,
+    {
+        writer.write_tag::<R>(CHECKPOINT_TAG)?;
+        checkpoint::write_parameters(
+            writer,
+            self.num_seed_nodes,
+            self.num_total_nodes,
+            self.initial_degree,
+            self.without_replacement,
+        )?;
+
+        writer.write_u64(self.next_node as u64)?;
+        writer.write_nodes(&self.degrees[..self.next_node as usize])?;
+        writer.write_rng(&self.rng)
+    }
+}
+
+/// Clears `hosts` and fills it with `number` nodes drawn uniformly from `0..new_node`
+pub(super) fn sample_uniform_hosts(
+    rng: &mut impl Rng,
+    hosts: &mut Vec<Node>,
+    new_node: Node,
+    number: Node,
+    without_replacement: bool,
+) {
+    hosts.clear();
+
+    while hosts.len() < number as usize {
+        let host = rng.gen_range(0..new_node);
+
+        if without_replacement && hosts.contains(&host) {
+            continue;
+        }
+
+        hosts.push(host);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::edge_writer::DegreeCount;
+    use itertools::Itertools;
+    use pcg_rand::Pcg64;
+    use rand::SeedableRng;
+
+    #[test]
+    fn without_replacement_has_no_multi_edges() {
+        let mut rng = Pcg64::seed_from_u64(1234);
+        let mut hosts = Vec::new();
+
+        for new_node in 5..1000 {
+            sample_uniform_hosts(&mut rng, &mut hosts, new_node, 5, true);
+            assert_eq!(hosts.len(), 5);
+            assert!(hosts.iter().all_unique());
+            assert!(hosts.iter().all(|&h| h < new_node));
+        }
+    }
+
+    #[test]
+    fn degree_sum() {
+        for without_replacement in [false, true] {
+            let mut algo = AlgoUniform::new(
+                Pcg64::seed_from_u64(4321),
+                1,
+                10,
+                1000,
+                3,
+                without_replacement,
+                false,
+                WeightFunction::new(0.0, 0.0),
+            );
+            algo.set_seed_graph_degrees((0..10).map(|_| 1));
+
+            let mut writer = DegreeCount::new(1010);
+            algo.run(&mut writer);
+
+            assert_eq!(writer.number_of_edges(), 3000);
+            assert_eq!(algo.degrees().iter().sum::<Node>(), 10 + 2 * 3000);
+        }
+    }
+}
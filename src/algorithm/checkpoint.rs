@@ -0,0 +1,363 @@
+//! Checkpoints of sequential generation runs. An algorithm with checkpoints enabled saves its
+//! complete state (node infos, proposal list, accumulated weights and the state of its random
+//! number generator) every `interval` nodes; resuming from the checkpoint continues with the same
+//! random choices and thus yields the graph of an uninterrupted run. Edges written before the
+//! checkpoint are not written again.
+//!
+//! The parallel algorithms do not support checkpoints: their threads would have to pause at a
+//! common node to save a consistent state, and a resumed run of parallel PolyPA with several
+//! threads could not repeat the uninterrupted one anyway. Likewise, the state of the
+//! distributed algorithm is spread over several processes.
+//!
+//! A checkpoint consists of a tag identifying the algorithm and the type of its generator (a
+//! checkpoint resumed with another generator would silently yield a different graph) followed
+//! by the fields in the order in which the algorithm writes them, as little-endian 64-bit
+//! values; the generator is encoded by `bincode`. It is written to a temporary file that replaces the previous checkpoint only
+//! once complete, so a crash while saving leaves the previous checkpoint intact.
+
+use super::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::type_name;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"NLPACKPT";
+
+#[derive(Clone, Debug)]
+pub struct CheckpointConfig {
+    pub path: PathBuf,
+    /// Number of nodes between two checkpoints
+    pub interval: Node,
+}
+
+/// Saves checkpoints of an algorithm `A` by `save`, which is instantiated where the generator is
+/// known to be serializable
+pub(super) struct Checkpointer<A> {
+    config: CheckpointConfig,
+    save: fn(&A, &mut CheckpointWriter) -> io::Result<()>,
+}
+
+impl<A> Checkpointer<A> {
+    pub(super) fn new(
+        config: CheckpointConfig,
+        save: fn(&A, &mut CheckpointWriter) -> io::Result<()>,
+    ) -> Self {
+        assert!(config.interval > 0);
+        Self { config, save }
+    }
+
+    /// Saves a checkpoint if `next_node`, the first node not yet inserted, is a multiple of the
+    /// interval and some nodes remain; returns whether it did
+    pub(super) fn save_if_due(&self, algo: &A, next_node: Node, num_total_nodes: Node) -> bool {
+        if !next_node.is_multiple_of(self.config.interval) || next_node >= num_total_nodes {
+            return false;
+        }
+
+        let path = &self.config.path;
+        CheckpointWriter::create(path)
+            .and_then(|mut writer| {
+                (self.save)(algo, &mut writer)?;
+                writer.finish()
+            })
+            .unwrap_or_else(|e| panic!("Cannot write checkpoint {}: {}", path.display(), e));
+
+        println!("Checkpoint before node {}", next_node);
+        true
+    }
+}
+
+pub(super) struct CheckpointWriter {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    tmp_path: PathBuf,
+}
+
+impl CheckpointWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(MAGIC)?;
+
+        Ok(Self {
+            writer,
+            path: path.to_owned(),
+            tmp_path,
+        })
+    }
+
+    /// Writes the tag of the algorithm and the type of its generator `R`
+    pub(super) fn write_tag<R>(&mut self, tag: &str) -> io::Result<()> {
+        self.write_str(tag)?;
+        self.write_str(type_name::<R>())
+    }
+
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        self.write_u64(s.len() as u64)?;
+        self.writer.write_all(s.as_bytes())
+    }
+
+    pub(super) fn write_u64(&mut self, value: u64) -> io::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())
+    }
+
+    pub(super) fn write_f64(&mut self, value: f64) -> io::Result<()> {
+        self.write_u64(value.to_bits())
+    }
+
+    /// Writes the length followed by the elements
+    pub(super) fn write_nodes(&mut self, nodes: &[Node]) -> io::Result<()> {
+        self.write_u64(nodes.len() as u64)?;
+        for &node in nodes {
+            self.write_u64(node as u64)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn write_rng<R: Serialize>(&mut self, rng: &R) -> io::Result<()> {
+        bincode::serialize_into(&mut self.writer, rng).map_err(io::Error::other)
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        std::fs::rename(&self.tmp_path, &self.path)
+    }
+}
+
+pub(super) struct CheckpointReader {
+    reader: BufReader<File>,
+}
+
+impl CheckpointReader {
+    pub(super) fn open(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a checkpoint".to_string()));
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// Fails unless the checkpoint was written by the algorithm identified by `tag` with a
+    /// generator of type `R`
+    pub(super) fn expect_tag<R>(&mut self, tag: &str) -> io::Result<()> {
+        self.expect_str(tag, "algorithm")?;
+        self.expect_str(type_name::<R>(), "generator")
+    }
+
+    fn expect_str(&mut self, expected: &str, what: &str) -> io::Result<()> {
+        let len = self.read_u64()? as usize;
+        let mut found = vec![0u8; len.min(1024)];
+        self.reader.read_exact(&mut found)?;
+
+        if found != expected.as_bytes() {
+            return Err(invalid_data(format!(
+                "{} is {} in the checkpoint, but {} in this run",
+                what,
+                String::from_utf8_lossy(&found),
+                expected
+            )));
+        }
+        Ok(())
+    }
+
+    pub(super) fn read_u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub(super) fn read_f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_bits(self.read_u64()?))
+    }
+
+    pub(super) fn read_node(&mut self) -> io::Result<Node> {
+        Ok(self.read_u64()? as Node)
+    }
+
+    /// Fails unless the next value equals `expected`, e.g. a parameter of the run
+    pub(super) fn expect_u64(&mut self, expected: u64, what: &str) -> io::Result<()> {
+        let found = self.read_u64()?;
+        if found != expected {
+            return Err(invalid_data(format!(
+                "{} is {} in the checkpoint, but {} in this run",
+                what, found, expected
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads nodes written by [`CheckpointWriter::write_nodes`] and passes them to `push`
+    pub(super) fn read_nodes(&mut self, mut push: impl FnMut(Node)) -> io::Result<()> {
+        let len = self.read_u64()?;
+        for _ in 0..len {
+            push(self.read_node()?);
+        }
+        Ok(())
+    }
+
+    pub(super) fn read_rng<R: DeserializeOwned>(&mut self) -> io::Result<R> {
+        bincode::deserialize_from(&mut self.reader).map_err(io::Error::other)
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Writes the parameters that determine the graph besides the random choices
+pub(super) fn write_parameters(
+    writer: &mut CheckpointWriter,
+    num_seed_nodes: Node,
+    num_total_nodes: Node,
+    initial_degree: Node,
+    without_replacement: bool,
+) -> io::Result<()> {
+    writer.write_u64(num_seed_nodes as u64)?;
+    writer.write_u64(num_total_nodes as u64)?;
+    writer.write_u64(initial_degree as u64)?;
+    writer.write_u64(without_replacement as u64)
+}
+
+/// Fails unless the parameters written by [`write_parameters`] match the given ones
+pub(super) fn expect_parameters(
+    reader: &mut CheckpointReader,
+    num_seed_nodes: Node,
+    num_total_nodes: Node,
+    initial_degree: Node,
+    without_replacement: bool,
+) -> io::Result<()> {
+    reader.expect_u64(num_seed_nodes as u64, "number of seed nodes")?;
+    reader.expect_u64(num_total_nodes as u64, "number of nodes")?;
+    reader.expect_u64(initial_degree as u64, "initial degree")?;
+    reader.expect_u64(without_replacement as u64, "sampling without replacement")
+}
+
+pub(super) fn write_weight_function(
+    writer: &mut CheckpointWriter,
+    weight_function: &WeightFunction,
+) -> io::Result<()> {
+    writer.write_f64(weight_function.exponent())?;
+    writer.write_f64(weight_function.offset())
+}
+
+pub(super) fn expect_weight_function(
+    reader: &mut CheckpointReader,
+    weight_function: &WeightFunction,
+) -> io::Result<()> {
+    reader.expect_u64(weight_function.exponent().to_bits(), "exponent (bits)")?;
+    reader.expect_u64(weight_function.offset().to_bits(), "offset (bits)")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::algo_dynamic_weighted_index::AlgoDynamicWeightedIndex;
+    use crate::algorithm::algo_poly_pa::AlgoPolyPa;
+    use crate::algorithm::algo_poly_pa_hubs::AlgoPolyPaHubs;
+    use crate::algorithm::algo_poly_pa_prefetch::AlgoPolyPaPrefetch;
+    use crate::algorithm::algo_uniform::AlgoUniform;
+    use crate::algorithm::node_info::CompactNodeInfo;
+    use crate::algorithm::storage::InMemory;
+    use crate::edge_writer::EdgeCounter;
+    use pcg_rand::{Pcg32, Pcg64};
+    use rand::SeedableRng;
+
+    const NUM_SEED_NODES: Node = 10;
+    const NUM_NODES: Node = 5000;
+
+    fn new_algo<A: Algorithm<Pcg64>>(exponent: f64, resample: bool) -> A {
+        A::new(
+            Pcg64::seed_from_u64(1234),
+            1,
+            NUM_SEED_NODES,
+            NUM_NODES,
+            3,
+            true,
+            resample,
+            WeightFunction::new(exponent, 1.0),
+        )
+    }
+
+    /// Runs `A` with checkpoints and resumes from the last one, which has to yield the degrees
+    /// of the uninterrupted run
+    fn resumed_run_matches<A: Algorithm<Pcg64>>(name: &str, exponent: f64, resample: bool) {
+        let path = std::env::temp_dir().join(format!(
+            "nlpa-checkpoint-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+
+        let mut algo = new_algo::<A>(exponent, resample);
+        algo.set_seed_graph_degrees((0..NUM_SEED_NODES).map(|u| 1 + u % 3));
+        algo.enable_checkpoints(CheckpointConfig {
+            path: path.clone(),
+            interval: 1000,
+        });
+        algo.run(&mut EdgeCounter::default());
+
+        let mut resumed = new_algo::<A>(exponent, resample);
+        resumed.resume_from_checkpoint(&path).unwrap();
+        resumed.run(&mut EdgeCounter::default());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(algo.degrees(), resumed.degrees(), "{}", name);
+    }
+
+    #[test]
+    fn resumed_runs_match_uninterrupted_ones() {
+        resumed_run_matches::<AlgoPolyPa<_>>("polypa", 1.0, false);
+        resumed_run_matches::<AlgoPolyPa<_, InMemory, CompactNodeInfo>>("resample", 0.5, true);
+        resumed_run_matches::<AlgoPolyPaPrefetch<_>>("prefetch", 1.5, false);
+        resumed_run_matches::<AlgoUniform<_>>("uniform", 0.0, false);
+        resumed_run_matches::<AlgoDynamicWeightedIndex<_>>("dyn", 1.5, false);
+        resumed_run_matches::<AlgoDynamicWeightedIndex<_>>("dyn-resample", 1.0, true);
+        resumed_run_matches::<AlgoPolyPaHubs<_>>("hubs", 2.0, false);
+    }
+
+    #[test]
+    fn checkpoint_of_other_parameters_is_rejected() {
+        let path = std::env::temp_dir().join(format!(
+            "nlpa-checkpoint-test-{}-rejected",
+            std::process::id()
+        ));
+
+        let mut algo = new_algo::<AlgoPolyPa<Pcg64>>(1.0, false);
+        algo.set_seed_graph_degrees((0..NUM_SEED_NODES).map(|u| 1 + u % 3));
+        algo.enable_checkpoints(CheckpointConfig {
+            path: path.clone(),
+            interval: 1000,
+        });
+        algo.run(&mut EdgeCounter::default());
+
+        assert!(new_algo::<AlgoPolyPa<Pcg64>>(0.5, false)
+            .resume_from_checkpoint(&path)
+            .is_err());
+        assert!(new_algo::<AlgoUniform<Pcg64>>(0.0, false)
+            .resume_from_checkpoint(&path)
+            .is_err());
+
+        // same algorithm and parameters, but another generator
+        let mut other_rng = AlgoPolyPa::<Pcg32>::new(
+            Pcg32::seed_from_u64(1234),
+            1,
+            NUM_SEED_NODES,
+            NUM_NODES,
+            3,
+            true,
+            false,
+            WeightFunction::new(1.0, 1.0),
+        );
+        let error = other_rng.resume_from_checkpoint(&path).unwrap_err();
+        assert!(error.to_string().starts_with("generator is"), "{}", error);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
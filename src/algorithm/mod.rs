@@ -1,12 +1,39 @@
 use super::prelude::*;
+use crate::edge_writer::degree_distribution;
 use crate::parameters::Parameters;
 use crate::weight_function::WeightFunction;
+use checkpoint::CheckpointConfig;
 use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
 
+#[allow(clippy::unnecessary_cast)]
+pub mod acceptance;
+#[allow(clippy::unnecessary_cast)]
+pub mod algo_distributed_poly_pa;
+#[allow(clippy::unnecessary_cast)]
 pub mod algo_dynamic_weighted_index;
+#[allow(clippy::unnecessary_cast)]
 pub mod algo_parallel_poly_pa;
+#[allow(clippy::unnecessary_cast)]
+pub mod algo_parallel_uniform;
+#[allow(clippy::unnecessary_cast)]
 pub mod algo_poly_pa;
+#[allow(clippy::unnecessary_cast)]
+pub mod algo_poly_pa_hubs;
+#[allow(clippy::unnecessary_cast)]
 pub mod algo_poly_pa_prefetch;
+#[allow(clippy::unnecessary_cast)]
+pub mod algo_uniform;
+pub mod checkpoint;
+#[allow(clippy::unnecessary_cast)]
+pub mod node_info;
+pub mod seed_sequence;
+pub mod storage;
+#[cfg(test)]
+mod test_util;
 
 pub trait Algorithm<R: Rng>: Sized {
     const IS_PARALLEL: bool;
@@ -25,30 +52,60 @@ pub trait Algorithm<R: Rng>: Sized {
     fn set_seed_graph_degrees(&mut self, degrees: impl Iterator<Item = Node>);
     fn run(&mut self, writer: &mut impl EdgeWriter);
 
+    /// Algorithms with options beyond those of [`Algorithm::new`] override this method and
+    /// apply them to the result of [`new_from_parameters`]
     fn from_parameters(rng: R, opt: &Parameters) -> Self {
-        let weight_function = WeightFunction::new(opt.exponent, opt.offset);
-        assert!(weight_function.get(1) > 0.0);
-        Self::new(
-            rng,
-            opt.num_threads.unwrap_or_else(|| {
-                if Self::IS_PARALLEL {
-                    num_cpus::get()
-                } else {
-                    1
-                }
-            }),
-            opt.seed_nodes.unwrap(),
-            opt.nodes,
-            opt.initial_degree,
-            opt.without_replacement,
-            opt.resample_previous,
-            weight_function,
-        )
+        new_from_parameters(rng, opt)
+    }
+
+    /// Saves a checkpoint every `config.interval` nodes during [`Algorithm::run`] (see
+    /// [`checkpoint`]); algorithms without checkpoints keep this default, which panics
+    fn enable_checkpoints(&mut self, _config: CheckpointConfig)
+    where
+        R: Serialize,
+    {
+        panic!("The algorithm does not support checkpoints");
+    }
+
+    /// Restores the state saved in the checkpoint at `path` instead of setting the seed graph;
+    /// fails if the checkpoint was written by another algorithm or for other parameters
+    fn resume_from_checkpoint(&mut self, _path: &Path) -> io::Result<()>
+    where
+        R: DeserializeOwned,
+    {
+        panic!("The algorithm does not support checkpoints");
     }
 
     fn degrees(&self) -> Vec<Node>;
 
+    /// Algorithms whose node infos may exceed main memory override this and the following
+    /// method to avoid materializing [`Algorithm::degrees`]
+    fn degree_distribution(&self) -> Vec<(usize, usize)> {
+        degree_distribution(self.degrees().into_iter())
+    }
+
+    fn degree_sum(&self) -> u64 {
+        self.degrees().iter().map(|&d| d as u64).sum()
+    }
+
     fn number_of_edges(&self) -> usize {
-        self.degrees().iter().sum::<usize>() / 2
+        (self.degree_sum() / 2) as usize
     }
 }
+
+/// Calls [`Algorithm::new`] with the options common to all algorithms
+pub fn new_from_parameters<R: Rng, A: Algorithm<R>>(rng: R, opt: &Parameters) -> A {
+    let weight_function = WeightFunction::new(opt.exponent, opt.offset);
+    assert!(weight_function.get(1) > 0.0);
+    A::new(
+        rng,
+        opt.num_threads
+            .unwrap_or_else(|| if A::IS_PARALLEL { num_cpus::get() } else { 1 }),
+        opt.seed_nodes.unwrap() as Node,
+        opt.nodes as Node,
+        opt.initial_degree as Node,
+        opt.without_replacement,
+        opt.resample_previous,
+        weight_function,
+    )
+}
@@ -0,0 +1,176 @@
+//! Per-node bookkeeping of the sequential PolyPA algorithms. Both layouts store a node's degree
+//! and its count (the number of its entries in the proposal list, including the implicit one);
+//! they differ in whether the derived quantities weight and excess are cached.
+//!
+//! [`FullNodeInfo`] caches both and takes 32 bytes (24 bytes with the feature `node-u32`), while
+//! [`CompactNodeInfo`] takes 8 bytes and recomputes them using the [`WeightFunction`] on each
+//! access. The latter is cheap for degrees that are precomputed by the weight function, and pays
+//! off if the node infos do not fit into cache anymore. In `node-info-benchmark` with exponents
+//! up to one, the compact layout is 5-20% faster for 10^6 nodes and, with prefetching, 4-10%
+//! faster for 10^7 nodes (but 2-6% slower without); for exponent 1.5 it is 15-35% slower, since
+//! the weights of the hubs are not precomputed.
+
+use super::*;
+
+pub trait NodeInfo: Copy + Default {
+    fn degree(&self) -> Node;
+    fn count(&self) -> Node;
+
+    /// Returns `weight_function.get(self.degree())`
+    fn weight(&self, weight_function: &WeightFunction) -> f64;
+
+    /// Returns `self.weight(weight_function) / self.count()`
+    fn excess(&self, weight_function: &WeightFunction) -> f64;
+
+    /// Sets the degree; the caller has to pass `weight == weight_function.get(degree)`
+    fn set_degree(&mut self, degree: Node, weight: f64);
+    fn set_count(&mut self, count: Node);
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FullNodeInfo {
+    degree: Node,
+    count: Node,
+    weight: f64,
+    excess: f64,
+}
+
+impl Default for FullNodeInfo {
+    fn default() -> Self {
+        Self {
+            degree: 0,
+            count: 1,
+            weight: 0.0,
+            excess: 0.0,
+        }
+    }
+}
+
+impl NodeInfo for FullNodeInfo {
+    #[inline]
+    fn degree(&self) -> Node {
+        self.degree
+    }
+
+    #[inline]
+    fn count(&self) -> Node {
+        self.count
+    }
+
+    #[inline]
+    fn weight(&self, _weight_function: &WeightFunction) -> f64 {
+        self.weight
+    }
+
+    #[inline]
+    fn excess(&self, _weight_function: &WeightFunction) -> f64 {
+        self.excess
+    }
+
+    #[inline]
+    fn set_degree(&mut self, degree: Node, weight: f64) {
+        self.degree = degree;
+        self.weight = weight;
+        self.excess = weight / self.count as f64;
+    }
+
+    #[inline]
+    fn set_count(&mut self, count: Node) {
+        self.count = count;
+        self.excess = self.weight / count as f64;
+    }
+}
+
+/// Requires that degrees and counts fit into 32 bits
+#[derive(Clone, Copy, Debug)]
+pub struct CompactNodeInfo {
+    degree: u32,
+    count: u32,
+}
+
+impl Default for CompactNodeInfo {
+    fn default() -> Self {
+        Self {
+            degree: 0,
+            count: 1,
+        }
+    }
+}
+
+impl NodeInfo for CompactNodeInfo {
+    #[inline]
+    fn degree(&self) -> Node {
+        self.degree as Node
+    }
+
+    #[inline]
+    fn count(&self) -> Node {
+        self.count as Node
+    }
+
+    #[inline]
+    fn weight(&self, weight_function: &WeightFunction) -> f64 {
+        weight_function.get(self.degree as Node)
+    }
+
+    #[inline]
+    fn excess(&self, weight_function: &WeightFunction) -> f64 {
+        self.weight(weight_function) / self.count as f64
+    }
+
+    #[inline]
+    fn set_degree(&mut self, degree: Node, _weight: f64) {
+        debug_assert!(degree <= u32::MAX as Node);
+        self.degree = degree as u32;
+    }
+
+    #[inline]
+    fn set_count(&mut self, count: Node) {
+        debug_assert!(count <= u32::MAX as Node);
+        self.count = count as u32;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::algo_poly_pa::AlgoPolyPa;
+    use crate::algorithm::algo_poly_pa_prefetch::AlgoPolyPaPrefetch;
+    use crate::algorithm::storage::InMemory;
+    use crate::edge_writer::EdgeCounter;
+    use pcg_rand::Pcg64;
+    use rand::SeedableRng;
+
+    fn degrees<A: Algorithm<Pcg64>>(exponent: f64, offset: f64) -> Vec<Node> {
+        let mut algo = A::new(
+            Pcg64::seed_from_u64(1234),
+            1,
+            10,
+            10000,
+            3,
+            true,
+            false,
+            WeightFunction::new(exponent, offset),
+        );
+        // non-uniform degrees, so that the proposal list is non-empty from the start
+        algo.set_seed_graph_degrees((0..10).map(|u| 1 + u % 3));
+        algo.run(&mut EdgeCounter::default());
+        algo.degrees()
+    }
+
+    #[test]
+    fn layouts_yield_identical_graphs() {
+        // weight and excess are computed by the same expressions, so the random choices agree
+        for (exponent, offset) in [(0.5, 0.0), (1.0, 2.0), (1.5, 0.5)] {
+            assert_eq!(
+                degrees::<AlgoPolyPa<_, InMemory, FullNodeInfo>>(exponent, offset),
+                degrees::<AlgoPolyPa<_, InMemory, CompactNodeInfo>>(exponent, offset)
+            );
+
+            assert_eq!(
+                degrees::<AlgoPolyPaPrefetch<_, FullNodeInfo>>(exponent, offset),
+                degrees::<AlgoPolyPaPrefetch<_, CompactNodeInfo>>(exponent, offset)
+            );
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Seeding of several generators from one master seed. The seeds are consecutive outputs of
+//! SplitMix64, which is a bijection of its counter; hence all seeds (and, for PCG, the stream
+//! increments contained in them) are pairwise distinct, and every generator receives a seed of
+//! its full width rather than one expanded from 64 bits.
+//!
+//! The parallel algorithms print the master of their sequence; see
+//! [`Parameters::seed_sequence`](crate::parameters::Parameters::seed_sequence) for the runs it
+//! replays.
+
+use rand::SeedableRng;
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+pub struct SeedSequence {
+    master: u64,
+    state: u64,
+}
+
+impl SeedSequence {
+    pub fn new(master: u64) -> Self {
+        Self {
+            master,
+            state: master,
+        }
+    }
+
+    pub fn master(&self) -> u64 {
+        self.master
+    }
+
+    /// Returns the next seed of type `R::Seed`
+    pub fn next_seed<R: SeedableRng>(&mut self) -> R::Seed {
+        let mut seed = R::Seed::default();
+        for chunk in seed.as_mut().chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        seed
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use itertools::Itertools;
+    use pcg_rand::Pcg64;
+
+    #[test]
+    fn seeds_follow_splitmix64() {
+        let mut seeds = SeedSequence::new(0);
+        assert_eq!(seeds.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(seeds.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+
+        // seeds of different generators are distinct and reproducible
+        let mut seeds = SeedSequence::new(42);
+        let mut again = SeedSequence::new(42);
+        let derived = (0..100)
+            .map(|_| seeds.next_seed::<Pcg64>().as_mut().to_vec())
+            .collect_vec();
+        assert!(derived.iter().all_unique());
+        assert_eq!(derived[0], again.next_seed::<Pcg64>().as_mut().to_vec());
+        assert_eq!(seeds.master(), 42);
+    }
+}
@@ -0,0 +1,297 @@
+//! Backends for the large per-node arrays (node infos and proposal list) of the sequential
+//! algorithms. [`InMemory`] uses plain vectors; [`ExternalMemory`] keeps the arrays in
+//! memory-mapped files and thus allows for graphs larger than main memory.
+
+use itertools::Itertools;
+use memmap2::{Advice, MmapMut};
+use std::fs::{File, OpenOptions};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub trait StorageArray<T: Copy>: Deref<Target = [T]> + DerefMut {
+    fn push(&mut self, value: T);
+
+    /// Removes all elements but keeps the capacity
+    fn clear(&mut self);
+
+    /// Whether reads of random elements may wait for I/O, so that announcing them in batches by
+    /// [`StorageArray::will_need`] pays off
+    fn reads_may_wait(&self) -> bool {
+        false
+    }
+
+    /// Hints that the elements at `sorted_indices` (in increasing order) are read soon
+    fn will_need(&self, _sorted_indices: impl Iterator<Item = usize>) {}
+}
+
+pub trait Storage {
+    type Array<T: Copy>: StorageArray<T>;
+
+    /// Returns an array of `len` copies of `value`; `name` is only used for diagnostics
+    fn filled<T: Copy>(name: &str, len: usize, value: T) -> Self::Array<T>;
+
+    /// Returns an empty array that can grow to `capacity` elements without reallocation
+    fn with_capacity<T: Copy>(name: &str, capacity: usize) -> Self::Array<T>;
+}
+
+pub struct InMemory;
+
+impl<T: Copy> StorageArray<T> for Vec<T> {
+    fn push(&mut self, value: T) {
+        Vec::push(self, value)
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self)
+    }
+}
+
+impl Storage for InMemory {
+    type Array<T: Copy> = Vec<T>;
+
+    fn filled<T: Copy>(_name: &str, len: usize, value: T) -> Self::Array<T> {
+        vec![value; len]
+    }
+
+    fn with_capacity<T: Copy>(_name: &str, capacity: usize) -> Self::Array<T> {
+        Vec::with_capacity(capacity)
+    }
+}
+
+/// Keeps arrays in memory-mapped files within `std::env::temp_dir()`; set the environment
+/// variable `TMPDIR` to place them on a sufficiently large disk. The files are unlinked right
+/// after creation, so they are removed as soon as the process terminates.
+///
+/// The kernel is advised to read sequentially during initialization and randomly afterwards;
+/// appending to the proposal list is sequential anyway. The random reads of rejection sampling
+/// are batched for arrays exceeding a quarter of the main memory, which are unlikely to stay in
+/// the page cache: the algorithm announces the pages of several proposals by
+/// [`StorageArray::will_need`] in increasing order before reading any of them, so the kernel
+/// issues their I/O concurrently rather than faulting one page at a time. For cached arrays,
+/// the system calls would cost more than the faults they avoid.
+pub struct ExternalMemory;
+
+impl Storage for ExternalMemory {
+    type Array<T: Copy> = MmapArray<T>;
+
+    fn filled<T: Copy>(name: &str, len: usize, value: T) -> Self::Array<T> {
+        let mut array = MmapArray::new(name, len);
+        array.advise(Advice::Sequential);
+        for _ in 0..len {
+            array.push(value);
+        }
+        array.advise(Advice::Random);
+        array
+    }
+
+    fn with_capacity<T: Copy>(name: &str, capacity: usize) -> Self::Array<T> {
+        let array = MmapArray::new(name, capacity);
+        array.advise(Advice::Random);
+        array
+    }
+}
+
+/// A growable array of plain-old-data backed by a memory-mapped (and unlinked) file
+pub struct MmapArray<T: Copy> {
+    file: File,
+    mmap: MmapMut,
+    len: usize,
+    capacity: usize,
+    page_size: usize,
+    /// Size beyond which the array is unlikely to stay in the page cache
+    cached_bytes: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> MmapArray<T> {
+    fn new(name: &str, capacity: usize) -> Self {
+        static FILE_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "rust-nlpa-{}-{}-{}.bin",
+            std::process::id(),
+            FILE_ID.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+
+        let file = Self::create_unlinked_file(&path)
+            .unwrap_or_else(|e| panic!("Cannot create external memory at {:?}: {}", path, e));
+
+        let capacity = capacity.max(1);
+        let mmap = Self::map(&file, capacity);
+
+        // safety: sysconf has no preconditions
+        let (page_size, num_pages) = unsafe {
+            (
+                libc::sysconf(libc::_SC_PAGESIZE).max(1) as usize,
+                libc::sysconf(libc::_SC_PHYS_PAGES).max(0) as usize,
+            )
+        };
+
+        Self {
+            file,
+            mmap,
+            len: 0,
+            capacity,
+            page_size,
+            cached_bytes: page_size * num_pages / 4,
+            _marker: PhantomData,
+        }
+    }
+
+    fn create_unlinked_file(path: &Path) -> std::io::Result<File> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        std::fs::remove_file(path)?;
+        Ok(file)
+    }
+
+    fn map(file: &File, capacity: usize) -> MmapMut {
+        // the file is sparse; blocks are only allocated once they are written to
+        file.set_len((capacity * std::mem::size_of::<T>()) as u64)
+            .expect("Cannot resize external memory");
+
+        // safety: the file is unlinked and hence cannot be modified by other processes
+        unsafe { MmapMut::map_mut(file) }.expect("Cannot map external memory")
+    }
+
+    fn advise(&self, advice: Advice) {
+        // advice is only a hint; ignore if unsupported
+        let _ = self.mmap.advise(advice);
+    }
+
+    /// Announces reads independently of the array size
+    #[cfg(test)]
+    pub(super) fn assume_uncached(&mut self) {
+        self.cached_bytes = 0;
+    }
+
+    fn grow(&mut self) {
+        self.capacity *= 2;
+        self.mmap = Self::map(&self.file, self.capacity);
+        self.advise(Advice::Random);
+    }
+}
+
+impl<T: Copy> StorageArray<T> for MmapArray<T> {
+    fn push(&mut self, value: T) {
+        if self.len == self.capacity {
+            self.grow();
+        }
+
+        // safety: len < capacity and the mapping spans capacity elements
+        unsafe {
+            (self.mmap.as_mut_ptr() as *mut T)
+                .add(self.len)
+                .write(value)
+        };
+        self.len += 1;
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn reads_may_wait(&self) -> bool {
+        self.len * std::mem::size_of::<T>() > self.cached_bytes
+    }
+
+    /// Advises `WillNeed` for each run of consecutive pages that hold the elements
+    fn will_need(&self, sorted_indices: impl Iterator<Item = usize>) {
+        if !self.reads_may_wait() {
+            return;
+        }
+
+        let size = std::mem::size_of::<T>();
+        let runs = sorted_indices
+            .map(|i| {
+                (
+                    i * size / self.page_size,
+                    ((i + 1) * size - 1) / self.page_size,
+                )
+            })
+            .coalesce(|(first, last), (next_first, next_last)| {
+                if next_first <= last + 1 {
+                    Ok((first, last.max(next_last)))
+                } else {
+                    Err(((first, last), (next_first, next_last)))
+                }
+            });
+
+        for (first, last) in runs {
+            let begin = first * self.page_size;
+            let end = ((last + 1) * self.page_size).min(self.mmap.len());
+            // advice is only a hint; ignore if unsupported
+            let _ = self.mmap.advise_range(Advice::WillNeed, begin, end - begin);
+        }
+    }
+}
+
+impl<T: Copy> Deref for MmapArray<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // safety: the mapping is page-aligned and the first len elements were initialized by push
+        unsafe { std::slice::from_raw_parts(self.mmap.as_ptr() as *const T, self.len) }
+    }
+}
+
+impl<T: Copy> DerefMut for MmapArray<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // safety: the mapping is page-aligned and the first len elements were initialized by
+        // push; the exclusive borrow of self ensures that the slice is not aliased
+        unsafe { std::slice::from_raw_parts_mut(self.mmap.as_mut_ptr() as *mut T, self.len) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn external_memory_grows() {
+        let mut array = ExternalMemory::with_capacity::<(u64, f64)>("test", 3);
+
+        for i in 0..10000 {
+            array.push((i, i as f64));
+        }
+
+        assert_eq!(array.len(), 10000);
+        assert!(array
+            .iter()
+            .enumerate()
+            .all(|(i, &(a, b))| a == i as u64 && b == i as f64));
+    }
+
+    #[test]
+    fn external_memory_will_need() {
+        let mut array = ExternalMemory::filled("test", 100_000, (1u64, 2u32));
+        assert!(!array.reads_may_wait());
+        array.assume_uncached();
+        assert!(array.reads_may_wait());
+
+        // runs of pages, pages shared by several elements, and the last element
+        array.will_need([0, 1, 2, 400, 401, 5000, 99_999].into_iter());
+        array.will_need(std::iter::empty());
+        assert!(array.iter().all(|&x| x == (1, 2)));
+    }
+
+    #[test]
+    fn external_memory_filled() {
+        let mut array = ExternalMemory::filled("test", 1000, 7u32);
+        assert!(array.iter().all(|&x| x == 7));
+
+        array[10] = 3;
+        array.push(5);
+        assert_eq!(
+            array.iter().map(|&x| x as usize).sum::<usize>(),
+            999 * 7 + 3 + 5
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Statistical comparison of the generators against [`AlgoDynamicWeightedIndex`], which samples
+//! exactly from the attachment distribution
+
+use super::algo_dynamic_weighted_index::AlgoDynamicWeightedIndex;
+use super::*;
+use crate::edge_writer::EdgeCounter;
+use itertools::Itertools;
+use pcg_rand::Pcg64;
+use rand::SeedableRng;
+
+/// Graphs of `num_seed_nodes` seed nodes of degree one and `num_rand_nodes` random nodes, which
+/// are generated for each seed in `0..repeats`
+pub(crate) struct Setup {
+    pub repeats: u64,
+    pub num_seed_nodes: Node,
+    pub num_rand_nodes: Node,
+    pub initial_degree: Node,
+    pub exponent: f64,
+}
+
+impl Setup {
+    /// Returns `A` seeded by `seed`, sampling without replacement, with the seed graph set
+    pub(crate) fn new_algo<A: Algorithm<Pcg64>>(&self, seed: u64, num_threads: usize) -> A {
+        let mut algo = A::new(
+            Pcg64::seed_from_u64(seed),
+            num_threads,
+            self.num_seed_nodes,
+            self.num_rand_nodes,
+            self.initial_degree,
+            true,
+            false,
+            WeightFunction::new(self.exponent, 1.0),
+        );
+        algo.set_seed_graph_degrees((0..self.num_seed_nodes).map(|_| 1));
+        algo
+    }
+
+    /// Asserts that the mean maximum degree and number of leaves of the graphs returned by
+    /// `degrees` for each seed are within four standard errors of those of the dynamic weighted
+    /// index
+    pub(crate) fn assert_matches_dynamic_weighted_index(
+        &self,
+        degrees: impl FnMut(u64) -> Vec<Node>,
+    ) {
+        let found = self.statistics((0..self.repeats).map(degrees));
+        let reference = self.statistics((0..self.repeats).map(|seed| {
+            let mut algo = self.new_algo::<AlgoDynamicWeightedIndex<_>>(seed, 1);
+            algo.run(&mut EdgeCounter::default());
+            algo.degrees()
+        }));
+
+        for ((f, f_err), (r, r_err)) in found.into_iter().zip(reference) {
+            assert!(
+                (f - r).abs() < 4.0 * (f_err.powi(2) + r_err.powi(2)).sqrt(),
+                "exponent {}: {:?} vs {:?}",
+                self.exponent,
+                found,
+                reference
+            );
+        }
+    }
+
+    /// Returns the mean and the standard error of the maximum degree and the number of leaves
+    fn statistics(&self, degrees: impl Iterator<Item = Vec<Node>>) -> [(f64, f64); 2] {
+        let samples = degrees
+            .map(|degrees| {
+                let max_degree = *degrees.iter().max().unwrap() as f64;
+                let num_leaves = degrees
+                    .iter()
+                    .filter(|&&d| d == self.initial_degree)
+                    .count() as f64;
+                [max_degree, num_leaves]
+            })
+            .collect_vec();
+
+        [0, 1].map(|i| {
+            let n = samples.len() as f64;
+            let mean = samples.iter().map(|s| s[i]).sum::<f64>() / n;
+            let var = samples.iter().map(|s| (s[i] - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            (mean, (var / n).sqrt())
+        })
+    }
+}
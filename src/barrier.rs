@@ -0,0 +1,179 @@
+//! Barriers synchronizing the threads of the parallel algorithms. Each thread owns a handle
+//! obtained by cloning the barrier; all handles have to call [`Barrier::wait`] equally often.
+//!
+//! [`SpinBarrier`] has the lowest latency as long as each thread has a core of its own, but
+//! burns the cores of waiting threads; if the machine is oversubscribed or shared, the
+//! descheduled threads are delayed by the spinning ones. [`SpinParkBarrier`] spins briefly and
+//! then blocks, while [`StdBarrier`] always blocks.
+
+use std::hint::spin_loop;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+pub trait Barrier: Clone + Send + 'static {
+    const LABEL: &'static str;
+
+    fn new(num_threads: usize) -> Self;
+
+    /// Blocks until all threads called `wait`
+    fn wait(&mut self);
+}
+
+#[derive(Eq, Clone, Copy, PartialEq, Debug)]
+pub enum BarrierKind {
+    Spin,
+    SpinPark,
+    Std,
+}
+
+impl FromStr for BarrierKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "spin" => Ok(BarrierKind::Spin),
+            "spin-park" => Ok(BarrierKind::SpinPark),
+            "std" => Ok(BarrierKind::Std),
+            _ => Err(format!("Unknown barrier type: {}", s)),
+        }
+    }
+}
+
+/// Busy-waits using [`hurdles::Barrier`]
+#[derive(Clone)]
+pub struct SpinBarrier(hurdles::Barrier);
+
+impl Barrier for SpinBarrier {
+    const LABEL: &'static str = "Spin";
+
+    fn new(num_threads: usize) -> Self {
+        Self(hurdles::Barrier::new(num_threads))
+    }
+
+    #[inline]
+    fn wait(&mut self) {
+        self.0.wait();
+    }
+}
+
+/// Blocks using [`std::sync::Barrier`]
+#[derive(Clone)]
+pub struct StdBarrier(Arc<std::sync::Barrier>);
+
+impl Barrier for StdBarrier {
+    const LABEL: &'static str = "Std";
+
+    fn new(num_threads: usize) -> Self {
+        Self(Arc::new(std::sync::Barrier::new(num_threads)))
+    }
+
+    #[inline]
+    fn wait(&mut self) {
+        self.0.wait();
+    }
+}
+
+/// A waiting thread polls `SPIN_LIMIT` times, then yields its core `YIELD_LIMIT` times (so that
+/// threads not yet arrived may run on an oversubscribed machine), and then blocks
+const SPIN_LIMIT: usize = 1 << 10;
+const YIELD_LIMIT: usize = 16;
+
+struct SpinParkInner {
+    num_threads: usize,
+    num_arrived: AtomicUsize,
+    generation: AtomicUsize,
+    lock: Mutex<()>,
+    released: Condvar,
+}
+
+/// Spins and yields for a bounded number of polls and then blocks on a condition variable
+#[derive(Clone)]
+pub struct SpinParkBarrier(Arc<SpinParkInner>);
+
+impl Barrier for SpinParkBarrier {
+    const LABEL: &'static str = "SpinPark";
+
+    fn new(num_threads: usize) -> Self {
+        assert!(num_threads > 0);
+        Self(Arc::new(SpinParkInner {
+            num_threads,
+            num_arrived: AtomicUsize::new(0),
+            generation: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+            released: Condvar::new(),
+        }))
+    }
+
+    fn wait(&mut self) {
+        let inner = &*self.0;
+        let generation = inner.generation.load(Ordering::Acquire);
+
+        if inner.num_arrived.fetch_add(1, Ordering::AcqRel) + 1 == inner.num_threads {
+            // no thread can arrive for the next generation before the current one is released
+            inner.num_arrived.store(0, Ordering::Relaxed);
+
+            let _guard = inner.lock.lock().unwrap();
+            inner.generation.fetch_add(1, Ordering::AcqRel);
+            inner.released.notify_all();
+            return;
+        }
+
+        for i in 0..SPIN_LIMIT + YIELD_LIMIT {
+            if inner.generation.load(Ordering::Acquire) != generation {
+                return;
+            }
+
+            if i < SPIN_LIMIT {
+                spin_loop();
+            } else {
+                std::thread::yield_now();
+            }
+        }
+
+        let mut guard = inner.lock.lock().unwrap();
+        while inner.generation.load(Ordering::Acquire) == generation {
+            guard = inner.released.wait(guard).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use itertools::Itertools;
+
+    fn synchronizes<B: Barrier>() {
+        const NUM_THREADS: usize = 4;
+        const ROUNDS: usize = 1000;
+
+        let barrier = B::new(NUM_THREADS);
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let handles = (0..NUM_THREADS)
+            .map(|_| {
+                let mut barrier = barrier.clone();
+                let counter = counter.clone();
+                std::thread::spawn(move || {
+                    for round in 0..ROUNDS {
+                        counter.fetch_add(1, Ordering::AcqRel);
+                        barrier.wait();
+                        assert_eq!(counter.load(Ordering::Acquire), (round + 1) * NUM_THREADS);
+                        barrier.wait();
+                    }
+                })
+            })
+            .collect_vec();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn all_barriers_synchronize() {
+        synchronizes::<SpinBarrier>();
+        synchronizes::<SpinParkBarrier>();
+        synchronizes::<StdBarrier>();
+    }
+}
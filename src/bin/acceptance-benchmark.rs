@@ -0,0 +1,92 @@
+//! Compares the scalar and the batched acceptance tests of [`rust_nlpa::algorithm::acceptance`]
+//! on synthetic node infos. Proposals are uniform nodes whose excesses are uniform in an
+//! interval with the given mean acceptance probability; the number of nodes determines whether
+//! the node infos fit into cache.
+//!
+//! Output lines have the form `path,nodes,acceptance,ns_per_host`.
+
+#![feature(core_intrinsics)]
+#![allow(internal_features)]
+// conversions between `Node` and `usize` are no-ops unless the feature `node-u32` is enabled
+#![allow(clippy::unnecessary_cast)]
+
+use pcg_rand::Pcg64;
+use rand::prelude::*;
+use rust_nlpa::algorithm::acceptance::{sample_batched, sample_scalar};
+use rust_nlpa::prelude::*;
+use std::time::Instant;
+
+const NUM_REPEATS: u64 = 3;
+const NUM_HOSTS: usize = 2_000_000;
+const NODES: [usize; 3] = [10_000, 1_000_000, 20_000_000];
+const ACCEPTANCES: [f64; 3] = [0.9, 0.5, 0.1];
+
+const SCALE: f64 = 2.0 * (1u64 << 63) as f64;
+
+/// Excesses with the mean `acceptance` and maximum at most one (the assumed `wmax`)
+fn excesses(rng: &mut impl Rng, num_nodes: usize, acceptance: f64) -> Vec<f64> {
+    let range = if acceptance >= 0.5 {
+        (2.0 * acceptance - 1.0)..1.0
+    } else {
+        0.0..(2.0 * acceptance)
+    };
+    (0..num_nodes)
+        .map(|_| rng.gen_range(range.clone()))
+        .collect()
+}
+
+/// Uniform node whose excess is prefetched
+#[inline]
+fn propose(rng: &mut Pcg64, excesses: &[f64]) -> Node {
+    let proposal = rng.gen_range(0..excesses.len());
+    unsafe { std::intrinsics::prefetch_read_data(excesses.as_ptr().add(proposal), 1) };
+    proposal as Node
+}
+
+fn benchmark(
+    path: &str,
+    excesses: &[f64],
+    acceptance: f64,
+    seed: u64,
+    sample: impl Fn(&mut Pcg64) -> Node,
+) {
+    let mut rng = Pcg64::seed_from_u64(seed);
+
+    let start = Instant::now();
+    let mut checksum: u64 = 0;
+    for _ in 0..NUM_HOSTS {
+        checksum = checksum.wrapping_add(sample(&mut rng) as u64);
+    }
+    let elapsed = start.elapsed();
+    assert!(checksum > 0);
+
+    println!(
+        "{},{},{},{}",
+        path,
+        excesses.len(),
+        acceptance,
+        elapsed.as_nanos() as f64 / NUM_HOSTS as f64
+    );
+}
+
+fn main() {
+    for seed in 0..NUM_REPEATS {
+        for num_nodes in NODES {
+            for acceptance in ACCEPTANCES {
+                let excesses = excesses(&mut Pcg64::seed_from_u64(seed), num_nodes, acceptance);
+
+                let excess = |u: Node| excesses[u as usize];
+
+                benchmark("scalar", &excesses, acceptance, seed, |rng| {
+                    sample_scalar(rng, SCALE, |rng| propose(rng, &excesses), excess).host
+                });
+                benchmark("batched4", &excesses, acceptance, seed, |rng| {
+                    sample_batched::<_, 4>(rng, SCALE, |rng| propose(rng, &excesses), excess).host
+                });
+                benchmark("batched8", &excesses, acceptance, seed, |rng| {
+                    sample_batched::<_, 8>(rng, SCALE, |rng| propose(rng, &excesses), excess).host
+                });
+            }
+        }
+    }
+}
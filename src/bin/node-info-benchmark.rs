@@ -0,0 +1,83 @@
+//! Compares the node info layouts of the sequential PolyPA algorithms. The compact layout has a
+//! smaller cache footprint but recomputes weights; the latter is cheap for small degrees (which
+//! are precomputed by the weight function) and expensive otherwise, so the trade-off depends on
+//! both the number of nodes and the exponent.
+//!
+//! Output lines have the form `layout,algo,nodes,exponent,runtime_s`.
+
+use pcg_rand::Pcg64;
+use rand::SeedableRng;
+use rust_nlpa::algorithm::algo_poly_pa::AlgoPolyPa;
+use rust_nlpa::algorithm::algo_poly_pa_prefetch::AlgoPolyPaPrefetch;
+use rust_nlpa::algorithm::node_info::{CompactNodeInfo, FullNodeInfo};
+use rust_nlpa::algorithm::storage::InMemory;
+use rust_nlpa::algorithm::Algorithm;
+use rust_nlpa::edge_writer::EdgeCounter;
+use rust_nlpa::prelude::*;
+use std::time::Instant;
+
+const NUM_REPEATS: u64 = 3;
+const NUM_SEED_NODES: Node = 20;
+const INITIAL_DEGREE: Node = 2;
+const NODES: [Node; 3] = [100_000, 1_000_000, 10_000_000];
+const EXPONENTS: [f64; 4] = [0.5, 0.75, 1.0, 1.5];
+
+fn benchmark<A: Algorithm<Pcg64>>(
+    layout: &str,
+    algo: &str,
+    num_nodes: Node,
+    exponent: f64,
+    seed: u64,
+) {
+    let mut algorithm = A::new(
+        Pcg64::seed_from_u64(seed),
+        1,
+        NUM_SEED_NODES,
+        num_nodes,
+        INITIAL_DEGREE,
+        true,
+        false,
+        WeightFunction::new(exponent, 0.0),
+    );
+    algorithm.set_seed_graph_degrees((0..NUM_SEED_NODES).map(|_| 1));
+
+    let start = Instant::now();
+    algorithm.run(&mut EdgeCounter::default());
+    let elapsed = start.elapsed();
+
+    println!(
+        "{},{},{},{},{}",
+        layout,
+        algo,
+        num_nodes,
+        exponent,
+        elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    println!(
+        "# size_of full: {}b, compact: {}b",
+        std::mem::size_of::<FullNodeInfo>(),
+        std::mem::size_of::<CompactNodeInfo>()
+    );
+
+    for seed in 0..NUM_REPEATS {
+        for num_nodes in NODES {
+            for exponent in EXPONENTS {
+                benchmark::<AlgoPolyPa<_, InMemory, FullNodeInfo>>(
+                    "full", "polypa", num_nodes, exponent, seed,
+                );
+                benchmark::<AlgoPolyPa<_, InMemory, CompactNodeInfo>>(
+                    "compact", "polypa", num_nodes, exponent, seed,
+                );
+                benchmark::<AlgoPolyPaPrefetch<_, FullNodeInfo>>(
+                    "full", "prefetch", num_nodes, exponent, seed,
+                );
+                benchmark::<AlgoPolyPaPrefetch<_, CompactNodeInfo>>(
+                    "compact", "prefetch", num_nodes, exponent, seed,
+                );
+            }
+        }
+    }
+}
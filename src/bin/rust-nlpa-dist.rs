@@ -0,0 +1,152 @@
+use rust_nlpa::algorithm::algo_distributed_poly_pa::{Address, AlgoDistributedPolyPa};
+use rust_nlpa::edge_writer::report_distribution;
+use rust_nlpa::parameters::{check_options, Parameters, SamplingAlgorithm};
+use rust_nlpa::weight_function::WeightFunction;
+use rust_nlpa::Node;
+use std::io::stdout;
+use std::process::{Child, Command};
+use std::time::Instant;
+
+use pcg_rand::Pcg64;
+use rand::{Rng, SeedableRng};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "non_linear_preferential_attachment_distributed",
+    about = "Generates an edge list using non-linear preferential attachment on multiple processes"
+)]
+struct DistributedParameters {
+    #[structopt(flatten)]
+    parameters: Parameters,
+
+    /// Number of processes
+    #[structopt(long, default_value = "2")]
+    processes: usize,
+
+    /// Rank of this process; if omitted, the remaining processes are spawned on localhost
+    #[structopt(long)]
+    rank: Option<usize>,
+
+    /// Either tcp:<host>:<port> (process i listens on port+i) or unix:<path> (uses path.i)
+    #[structopt(long, default_value = "tcp:127.0.0.1:7340")]
+    address: Address,
+}
+
+fn spawn_local_processes(num_processes: usize) -> Vec<Child> {
+    let exe = std::env::current_exe().unwrap();
+    let args: Vec<_> = std::env::args().skip(1).collect();
+
+    (1..num_processes)
+        .map(|rank| {
+            Command::new(&exe)
+                .args(&args)
+                .arg("--rank")
+                .arg(rank.to_string())
+                .spawn()
+                .unwrap()
+        })
+        .collect()
+}
+
+fn execute(rank: usize, dist: &DistributedParameters) -> std::io::Result<()> {
+    let opt = &dist.parameters;
+
+    // all processes derive distinct streams from the same seed value
+    let rng = if let Some(seed_value) = opt.seed_value {
+        let mut seeder = Pcg64::seed_from_u64(seed_value);
+        let seeds: Vec<u64> = (0..=rank).map(|_| seeder.gen()).collect();
+        Pcg64::seed_from_u64(seeds[rank])
+    } else {
+        Pcg64::from_entropy()
+    };
+
+    let weight_function = WeightFunction::new(opt.exponent, opt.offset);
+    assert!(weight_function.get(1) > 0.0);
+
+    let mut algorithm = AlgoDistributedPolyPa::new(
+        rng,
+        &dist.address,
+        rank,
+        dist.processes,
+        opt.seed_nodes.unwrap() as Node,
+        opt.nodes as Node,
+        opt.initial_degree as Node,
+        opt.without_replacement,
+        weight_function,
+    )?;
+
+    // 1-regular graph
+    algorithm.set_seed_graph_degrees((0..opt.seed_nodes.unwrap()).map(|_| 1))?;
+
+    let start = Instant::now();
+    algorithm.run()?;
+    let runtime = start.elapsed();
+
+    if opt.report_degree_distribution {
+        if let Some(distr) = algorithm.degree_distribution()? {
+            report_distribution(&distr, &mut stdout().lock())?;
+        }
+    }
+
+    assert_eq!(
+        algorithm.degree_sum()?,
+        (opt.seed_nodes.unwrap() + 2 * opt.nodes * opt.initial_degree) as u64
+    );
+
+    if rank == 0 {
+        println!("runtime_s:{}", runtime.as_secs_f64());
+    }
+
+    algorithm.finish()
+}
+
+/// Options of [`Parameters`] that select or configure the shared-memory algorithms
+const UNSUPPORTED_OPTIONS: [&str; 12] = [
+    "algorithm",
+    "num-threads",
+    "rng",
+    "seed-sequence",
+    "barrier",
+    "pin-threads",
+    "batched-acceptance",
+    "prefetch-depth",
+    "telemetry",
+    "checkpoint",
+    "checkpoint-interval",
+    "resume",
+];
+
+fn main() {
+    let matches = DistributedParameters::clap().get_matches();
+    for option in UNSUPPORTED_OPTIONS {
+        assert!(
+            matches.occurrences_of(option) == 0,
+            "--{} is not supported by the distributed algorithm",
+            option
+        );
+    }
+
+    let mut dist = DistributedParameters::from_clap(&matches);
+    // the checks of the shared-memory counterpart apply
+    dist.parameters.algorithm = SamplingAlgorithm::ParallelPolyPa;
+    dist.parameters = check_options(dist.parameters);
+
+    assert!(dist.processes > 0);
+    assert!(!dist.parameters.resample_previous);
+
+    match dist.rank {
+        Some(rank) => {
+            assert!(rank < dist.processes);
+            execute(rank, &dist).unwrap();
+        }
+        None => {
+            let children = spawn_local_processes(dist.processes);
+            execute(0, &dist).unwrap();
+
+            for mut child in children {
+                assert!(child.wait().unwrap().success());
+            }
+        }
+    }
+}
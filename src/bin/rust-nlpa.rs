@@ -1,21 +1,55 @@
-use rust_nlpa::parameters::{get_and_check_options, Parameters, SamplingAlgorithm};
+use rust_nlpa::parameters::{get_and_check_options, Parameters, RngKind, SamplingAlgorithm};
 use std::io::stdout;
 use std::time::Instant;
 
-use pcg_rand::Pcg64;
-use rand::SeedableRng;
+use pcg_rand::{Pcg32, Pcg64};
+use rand::{Rng, SeedableRng};
+use rand_chacha::{ChaCha20Rng, ChaCha8Rng};
+use rand_xoshiro::Xoshiro256PlusPlus;
 use rust_nlpa::algorithm::algo_dynamic_weighted_index::AlgoDynamicWeightedIndex;
+use rust_nlpa::algorithm::algo_parallel_poly_pa::telemetry::{EpochTelemetry, NoTelemetry};
 use rust_nlpa::algorithm::algo_parallel_poly_pa::AlgoParallelPolyPa;
+use rust_nlpa::algorithm::algo_parallel_uniform::AlgoParallelUniform;
 use rust_nlpa::algorithm::algo_poly_pa::AlgoPolyPa;
-use rust_nlpa::algorithm::algo_poly_pa_prefetch::AlgoPolyPaPrefetch;
+use rust_nlpa::algorithm::algo_poly_pa_hubs::AlgoPolyPaHubs;
+use rust_nlpa::algorithm::algo_poly_pa_prefetch::{
+    calibrate_prefetch_depth, AlgoPolyPaPrefetch, PrefetchDepth, DEFAULT_PREFETCH_DEPTH,
+};
+use rust_nlpa::algorithm::algo_uniform::AlgoUniform;
+use rust_nlpa::algorithm::checkpoint::CheckpointConfig;
+use rust_nlpa::algorithm::node_info::{CompactNodeInfo, FullNodeInfo, NodeInfo};
+use rust_nlpa::algorithm::storage::{ExternalMemory, InMemory};
 use rust_nlpa::algorithm::Algorithm;
-use rust_nlpa::edge_writer::{degree_distribution, report_distribution, EdgeCounter};
+use rust_nlpa::barrier::{Barrier, BarrierKind, SpinBarrier, SpinParkBarrier, StdBarrier};
+use rust_nlpa::edge_writer::{report_distribution, EdgeCounter};
+use rust_nlpa::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
-fn execute<R: rand::Rng, T: Algorithm<R>>(rng: R, opt: &Parameters) {
+fn execute<R, T>(rng: R, opt: &Parameters)
+where
+    R: Rng + Serialize + DeserializeOwned,
+    T: Algorithm<R>,
+{
+    let setup_start = Instant::now();
     let mut algorithm = T::from_parameters(rng, opt);
 
-    // 1-regular graph
-    algorithm.set_seed_graph_degrees((0..opt.seed_nodes.unwrap()).into_iter().map(|_| 1));
+    if let Some(path) = &opt.resume {
+        algorithm
+            .resume_from_checkpoint(path)
+            .unwrap_or_else(|e| panic!("Cannot resume from {}: {}", path.display(), e));
+    } else {
+        // 1-regular graph
+        algorithm.set_seed_graph_degrees((0..opt.seed_nodes.unwrap()).into_iter().map(|_| 1));
+    }
+
+    if let Some(path) = &opt.checkpoint {
+        algorithm.enable_checkpoints(CheckpointConfig {
+            path: path.clone(),
+            interval: opt.checkpoint_interval as Node,
+        });
+    }
+    println!("setup_s:{}", setup_start.elapsed().as_secs_f64());
 
     let runtime = {
         let mut writer = EdgeCounter::default();
@@ -24,16 +58,14 @@ fn execute<R: rand::Rng, T: Algorithm<R>>(rng: R, opt: &Parameters) {
         algorithm.run(&mut writer);
         let runtime = start.elapsed();
 
-        let degrees = algorithm.degrees();
-
         if opt.report_degree_distribution {
-            let distr = degree_distribution(degrees.iter().copied());
+            let distr = algorithm.degree_distribution();
             report_distribution(&distr, &mut stdout().lock()).unwrap();
         }
 
         assert_eq!(
-            degrees.iter().copied().sum::<usize>(),
-            opt.seed_nodes.unwrap() + 2 * opt.nodes * opt.initial_degree
+            algorithm.degree_sum(),
+            (opt.seed_nodes.unwrap() + 2 * opt.nodes * opt.initial_degree) as u64
         );
 
         runtime
@@ -42,19 +74,86 @@ fn execute<R: rand::Rng, T: Algorithm<R>>(rng: R, opt: &Parameters) {
     println!("runtime_s:{}", runtime.as_secs_f64());
 }
 
-fn main() {
-    let opt = get_and_check_options();
+/// Telemetry is a type parameter, so that it costs nothing if disabled
+fn execute_parallel_poly_pa<R, B: Barrier>(rng: R, opt: &Parameters)
+where
+    R: Rng + SeedableRng + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    if opt.telemetry.is_some() {
+        execute::<_, AlgoParallelPolyPa<_, B, EpochTelemetry>>(rng, opt)
+    } else {
+        execute::<_, AlgoParallelPolyPa<_, B, NoTelemetry>>(rng, opt)
+    }
+}
+
+/// The prefetch depth is a const generic; it is selected among the instantiated depths
+fn execute_poly_pa_prefetch<R, I: NodeInfo>(rng: R, opt: &Parameters)
+where
+    R: Rng + SeedableRng + Serialize + DeserializeOwned,
+{
+    let depth = match opt.prefetch_depth {
+        None => DEFAULT_PREFETCH_DEPTH,
+        Some(PrefetchDepth::Fixed(depth)) => depth,
+        Some(PrefetchDepth::Calibrate) => {
+            let depth = calibrate_prefetch_depth::<R, I>(opt);
+            println!("Calibrated prefetch depth: {}", depth);
+            depth
+        }
+    };
 
+    match depth {
+        2 => execute::<_, AlgoPolyPaPrefetch<_, I, 2>>(rng, opt),
+        4 => execute::<_, AlgoPolyPaPrefetch<_, I, 4>>(rng, opt),
+        8 => execute::<_, AlgoPolyPaPrefetch<_, I, 8>>(rng, opt),
+        16 => execute::<_, AlgoPolyPaPrefetch<_, I, 16>>(rng, opt),
+        32 => execute::<_, AlgoPolyPaPrefetch<_, I, 32>>(rng, opt),
+        _ => unreachable!("checked when parsing the prefetch depth"),
+    }
+}
+
+fn execute_with_rng<R>(opt: &Parameters)
+where
+    R: Rng + SeedableRng + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
     let rng = if let Some(seed_value) = opt.seed_value {
-        Pcg64::seed_from_u64(seed_value)
+        R::seed_from_u64(seed_value)
     } else {
-        Pcg64::from_entropy()
+        R::from_entropy()
     };
 
     match opt.algorithm {
-        SamplingAlgorithm::DynWeightIndex => execute::<_, AlgoDynamicWeightedIndex<_>>(rng, &opt),
-        SamplingAlgorithm::PolyPA => execute::<_, AlgoPolyPa<_>>(rng, &opt),
-        SamplingAlgorithm::PolyPAPrefetch => execute::<_, AlgoPolyPaPrefetch<_>>(rng, &opt),
-        SamplingAlgorithm::ParallelPolyPa => execute::<_, AlgoParallelPolyPa<_>>(rng, &opt),
+        SamplingAlgorithm::DynWeightIndex => execute::<_, AlgoDynamicWeightedIndex<_>>(rng, opt),
+        SamplingAlgorithm::PolyPA => execute::<_, AlgoPolyPa<_>>(rng, opt),
+        SamplingAlgorithm::PolyPAPrefetch => execute_poly_pa_prefetch::<_, FullNodeInfo>(rng, opt),
+        SamplingAlgorithm::PolyPACompact => {
+            execute::<_, AlgoPolyPa<_, InMemory, CompactNodeInfo>>(rng, opt)
+        }
+        SamplingAlgorithm::PolyPAPrefetchCompact => {
+            execute_poly_pa_prefetch::<_, CompactNodeInfo>(rng, opt)
+        }
+        SamplingAlgorithm::PolyPAExternalMemory => {
+            execute::<_, AlgoPolyPa<_, ExternalMemory>>(rng, opt)
+        }
+        SamplingAlgorithm::PolyPAHubs => execute::<_, AlgoPolyPaHubs<_>>(rng, opt),
+        SamplingAlgorithm::ParallelPolyPa => match opt.barrier {
+            BarrierKind::Spin => execute_parallel_poly_pa::<_, SpinBarrier>(rng, opt),
+            BarrierKind::SpinPark => execute_parallel_poly_pa::<_, SpinParkBarrier>(rng, opt),
+            BarrierKind::Std => execute_parallel_poly_pa::<_, StdBarrier>(rng, opt),
+        },
+        SamplingAlgorithm::Uniform => execute::<_, AlgoUniform<_>>(rng, opt),
+        SamplingAlgorithm::ParallelUniform => execute::<_, AlgoParallelUniform<_>>(rng, opt),
+        SamplingAlgorithm::Auto => unreachable!("resolved by get_and_check_options"),
     };
 }
+
+fn main() {
+    let opt = get_and_check_options();
+
+    match opt.rng {
+        RngKind::Pcg64 => execute_with_rng::<Pcg64>(&opt),
+        RngKind::Pcg32 => execute_with_rng::<Pcg32>(&opt),
+        RngKind::Xoshiro256PlusPlus => execute_with_rng::<Xoshiro256PlusPlus>(&opt),
+        RngKind::ChaCha8 => execute_with_rng::<ChaCha8Rng>(&opt),
+        RngKind::ChaCha20 => execute_with_rng::<ChaCha20Rng>(&opt),
+    }
+}
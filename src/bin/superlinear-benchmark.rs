@@ -0,0 +1,59 @@
+//! Compares the sequential algorithms for superlinear kernels, where a few hubs attract almost
+//! all edges: the plain PolyPA suffers from a high rejection rate, the dynamic weighted index
+//! from its logarithmic update costs, and the hub variant of PolyPA samples the hubs exactly.
+//!
+//! Output lines have the form `algo,nodes,exponent,runtime_s`.
+
+use pcg_rand::Pcg64;
+use rand::SeedableRng;
+use rust_nlpa::algorithm::algo_dynamic_weighted_index::AlgoDynamicWeightedIndex;
+use rust_nlpa::algorithm::algo_poly_pa::AlgoPolyPa;
+use rust_nlpa::algorithm::algo_poly_pa_hubs::AlgoPolyPaHubs;
+use rust_nlpa::algorithm::Algorithm;
+use rust_nlpa::edge_writer::EdgeCounter;
+use rust_nlpa::prelude::*;
+use std::time::Instant;
+
+const NUM_REPEATS: u64 = 3;
+const NUM_SEED_NODES: Node = 20;
+const INITIAL_DEGREE: Node = 2;
+const NODES: [Node; 3] = [100_000, 1_000_000, 10_000_000];
+const EXPONENTS: [f64; 5] = [1.5, 1.75, 2.0, 2.5, 3.0];
+
+fn benchmark<A: Algorithm<Pcg64>>(algo: &str, num_nodes: Node, exponent: f64, seed: u64) {
+    let mut algorithm = A::new(
+        Pcg64::seed_from_u64(seed),
+        1,
+        NUM_SEED_NODES,
+        num_nodes,
+        INITIAL_DEGREE,
+        true,
+        false,
+        WeightFunction::new(exponent, 0.0),
+    );
+    algorithm.set_seed_graph_degrees((0..NUM_SEED_NODES).map(|_| 1));
+
+    let start = Instant::now();
+    algorithm.run(&mut EdgeCounter::default());
+    let elapsed = start.elapsed();
+
+    println!(
+        "{},{},{},{}",
+        algo,
+        num_nodes,
+        exponent,
+        elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    for seed in 0..NUM_REPEATS {
+        for num_nodes in NODES {
+            for exponent in EXPONENTS {
+                benchmark::<AlgoPolyPa<_>>("polypa", num_nodes, exponent, seed);
+                benchmark::<AlgoDynamicWeightedIndex<_>>("dyn", num_nodes, exponent, seed);
+                benchmark::<AlgoPolyPaHubs<_>>("polypa-hubs", num_nodes, exponent, seed);
+            }
+        }
+    }
+}
@@ -28,7 +28,7 @@ impl EdgeCounter {
 #[derive(Clone, Debug)]
 pub struct DegreeCount {
     number_of_edges: usize,
-    degrees: Vec<usize>,
+    degrees: Vec<Node>,
 }
 
 impl DegreeCount {
@@ -39,7 +39,7 @@ impl DegreeCount {
         }
     }
 
-    pub fn degrees(&self) -> &[usize] {
+    pub fn degrees(&self) -> &[Node] {
         &self.degrees
     }
 
@@ -58,7 +58,11 @@ impl DegreeCount {
 }
 
 pub fn degree_distribution(degrees: impl Iterator<Item = Node>) -> Vec<(usize, usize)> {
-    let mut counts = degrees.counts().into_iter().collect_vec();
+    let mut counts = degrees
+        .map(|d| d as usize)
+        .counts()
+        .into_iter()
+        .collect_vec();
     counts.sort_unstable();
     counts
 }
@@ -80,7 +84,7 @@ pub fn report_distribution(
 impl EdgeWriter for DegreeCount {
     fn add_edge(&mut self, u: Node, v: Node) {
         self.number_of_edges += 1;
-        self.degrees[u] += 1;
-        self.degrees[v] += 1;
+        self.degrees[u as usize] += 1;
+        self.degrees[v as usize] += 1;
     }
 }
@@ -2,13 +2,27 @@
 #![feature(core_intrinsics)]
 #![feature(slice_take)]
 #![feature(iter_is_partitioned)]
+#![feature(portable_simd)]
 
+pub mod affinity;
 pub mod algorithm;
+pub mod barrier;
+#[allow(clippy::unnecessary_cast)]
 pub mod edge_writer;
 pub mod parameters;
+#[allow(clippy::unnecessary_cast)]
 pub mod weight_function;
 
+/// Node ids (and degrees) use 64 bits by default; the feature `node-u32` halves their memory
+/// footprint, restricting graphs to less than `u32::MAX` nodes.
+///
+/// Depending on the feature, conversions between `Node` and `usize` or `u32` are no-ops, so
+/// the modules performing them allow `clippy::unnecessary_cast`.
+#[cfg(not(feature = "node-u32"))]
 pub type Node = usize;
+#[cfg(feature = "node-u32")]
+pub type Node = u32;
+
 pub type Edge = (Node, Node);
 
 pub mod prelude {
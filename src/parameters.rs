@@ -1,3 +1,9 @@
+use crate::affinity::ThreadPinning;
+use crate::algorithm::algo_poly_pa_prefetch::PrefetchDepth;
+use crate::barrier::BarrierKind;
+use crate::weight_function::{KernelProperties, Monotonicity, WeightFunction};
+use crate::Node;
+use std::path::PathBuf;
 use std::str::FromStr;
 use structopt::StructOpt;
 
@@ -7,15 +13,28 @@ use structopt::StructOpt;
     about = "Generates an edge list using non-linear preferential attachment"
 )]
 pub struct Parameters {
+    /// Sampling algorithm; auto picks one suited for the other parameters
     #[structopt(short = "a", long, default_value = "dyn")]
     pub algorithm: SamplingAlgorithm,
 
     #[structopt(short = "i", long)]
     pub seed_nodes: Option<usize>,
 
+    /// Seed of the random number generator (from entropy if omitted). Runs are reproducible
+    /// except for par-polypa with several threads, which depends on the thread scheduling
     #[structopt(short = "s", long)]
     pub seed_value: Option<u64>,
 
+    /// Master seed (hexadecimal) of the streams of par-polypa and par-uniform, as printed by a
+    /// previous run; replays par-uniform runs with the same number of threads and par-polypa
+    /// runs with a single thread
+    #[structopt(long, parse(try_from_str = parse_hex))]
+    pub seed_sequence: Option<u64>,
+
+    /// Random number generator: pcg64, pcg32, xoshiro256++, chacha8 or chacha20
+    #[structopt(long, default_value = "pcg64")]
+    pub rng: RngKind,
+
     #[structopt(short = "n", long)]
     pub nodes: usize,
 
@@ -39,6 +58,45 @@ pub struct Parameters {
 
     #[structopt(short = "t", long)]
     pub num_threads: Option<usize>,
+
+    /// Barrier of the parallel PolyPA algorithm: spin, spin-park or std
+    #[structopt(long, default_value = "spin")]
+    pub barrier: BarrierKind,
+
+    /// Pins the threads of the parallel PolyPA algorithm: compact, scatter or a list of CPUs
+    /// (e.g. 0-3,8)
+    #[structopt(long)]
+    pub pin_threads: Option<ThreadPinning>,
+
+    /// Draws and tests proposals in batches; pays off for low acceptance rates (polypa and
+    /// par-polypa)
+    #[structopt(long)]
+    pub batched_acceptance: bool,
+
+    /// Look-ahead of the prefetching PolyPA algorithms: 2, 4, 8, 16, 32 or auto (benchmarks
+    /// the depths on this machine before the run)
+    #[structopt(long)]
+    pub prefetch_depth: Option<PrefetchDepth>,
+
+    /// Writes per-epoch measurements of the parallel PolyPA algorithm to this CSV file
+    #[structopt(long, parse(from_os_str))]
+    pub telemetry: Option<PathBuf>,
+
+    /// Saves a checkpoint to this file every `checkpoint-interval` nodes (sequential algorithms
+    /// only; auto then selects among these). The threads of par-polypa and par-uniform would
+    /// have to pause at a common node to save a consistent state, and par-polypa runs with
+    /// several threads cannot be repeated anyway; the state of rust-nlpa-dist is spread over
+    /// several processes.
+    #[structopt(long, parse(from_os_str))]
+    pub checkpoint: Option<PathBuf>,
+
+    #[structopt(long, default_value = "100000000")]
+    pub checkpoint_interval: usize,
+
+    /// Continues the run saved in this checkpoint; the other parameters have to match those of
+    /// the interrupted run. Only edges of nodes after the checkpoint are written.
+    #[structopt(long, parse(from_os_str))]
+    pub resume: Option<PathBuf>,
 }
 
 #[derive(Eq, Clone, Copy, PartialEq, Debug)]
@@ -46,9 +104,54 @@ pub enum SamplingAlgorithm {
     DynWeightIndex,
     PolyPA,
     PolyPAPrefetch,
+    PolyPACompact,
+    PolyPAPrefetchCompact,
+    PolyPAExternalMemory,
+    PolyPAHubs,
     ParallelPolyPa,
+    Uniform,
+    ParallelUniform,
+    Auto,
 }
 
+/// Generators selectable by `rust-nlpa`; the ChaCha variants are cryptographically secure but
+/// slower
+#[derive(Eq, Clone, Copy, PartialEq, Debug)]
+pub enum RngKind {
+    Pcg64,
+    Pcg32,
+    Xoshiro256PlusPlus,
+    ChaCha8,
+    ChaCha20,
+}
+
+impl FromStr for RngKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pcg64" => Ok(RngKind::Pcg64),
+            "pcg32" => Ok(RngKind::Pcg32),
+            "xoshiro256++" => Ok(RngKind::Xoshiro256PlusPlus),
+            "chacha8" => Ok(RngKind::ChaCha8),
+            "chacha20" => Ok(RngKind::ChaCha20),
+            _ => Err(format!("Unknown random number generator: {}", s)),
+        }
+    }
+}
+
+fn parse_hex(s: &str) -> Result<u64, String> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("Invalid hexadecimal number {}: {}", s, e))
+}
+
+/// Below this number of nodes, the parallel algorithm does not amortize its startup and
+/// synchronization costs
+const AUTO_MIN_NODES_PARALLEL: usize = 1_000_000;
+
+/// Beyond this number of nodes, the node infos do not fit into cache and prefetching pays off
+const AUTO_MIN_NODES_PREFETCH: usize = 10_000_000;
+
 impl FromStr for SamplingAlgorithm {
     type Err = String;
 
@@ -57,15 +160,146 @@ impl FromStr for SamplingAlgorithm {
             "dyn" => Ok(SamplingAlgorithm::DynWeightIndex),
             "polypa" => Ok(SamplingAlgorithm::PolyPA),
             "polypa-prefetch" => Ok(SamplingAlgorithm::PolyPAPrefetch),
+            "polypa-compact" => Ok(SamplingAlgorithm::PolyPACompact),
+            "polypa-prefetch-compact" => Ok(SamplingAlgorithm::PolyPAPrefetchCompact),
+            "polypa-em" => Ok(SamplingAlgorithm::PolyPAExternalMemory),
+            "polypa-hubs" => Ok(SamplingAlgorithm::PolyPAHubs),
             "par-polypa" => Ok(SamplingAlgorithm::ParallelPolyPa),
+            "uniform" => Ok(SamplingAlgorithm::Uniform),
+            "par-uniform" => Ok(SamplingAlgorithm::ParallelUniform),
+            "auto" => Ok(SamplingAlgorithm::Auto),
             _ => Err(format!("Unknown algorithm type: {}", s)),
         }
     }
 }
 
+impl SamplingAlgorithm {
+    pub fn is_parallel(self) -> bool {
+        matches!(
+            self,
+            SamplingAlgorithm::ParallelPolyPa | SamplingAlgorithm::ParallelUniform
+        )
+    }
+
+    /// Returns the specialized uniform attachment algorithm that matches `self` w.r.t. parallelism;
+    /// `-a auto` selects it for constant weights
+    pub fn for_constant_weights(self) -> Self {
+        if self.is_parallel() {
+            SamplingAlgorithm::ParallelUniform
+        } else {
+            SamplingAlgorithm::Uniform
+        }
+    }
+
+    /// Whether the algorithm stores degrees and counts in 32 bits (independently of `Node`)
+    pub fn uses_compact_node_info(self) -> bool {
+        matches!(
+            self,
+            SamplingAlgorithm::PolyPACompact | SamplingAlgorithm::PolyPAPrefetchCompact
+        )
+    }
+
+    /// Whether the algorithm implements [`Algorithm::enable_checkpoints`] and
+    /// [`Algorithm::resume_from_checkpoint`], i.e. whether it is sequential (see
+    /// [`checkpoint`](crate::algorithm::checkpoint))
+    ///
+    /// [`Algorithm::enable_checkpoints`]: crate::algorithm::Algorithm::enable_checkpoints
+    /// [`Algorithm::resume_from_checkpoint`]: crate::algorithm::Algorithm::resume_from_checkpoint
+    pub fn supports_checkpoints(self) -> bool {
+        !self.is_parallel()
+    }
+
+    /// Whether the algorithm samples from the correct distribution for kernels with the given
+    /// properties
+    pub fn supports_kernel(self, properties: KernelProperties) -> bool {
+        match self {
+            SamplingAlgorithm::ParallelPolyPa => properties.is_non_decreasing(),
+            SamplingAlgorithm::Uniform | SamplingAlgorithm::ParallelUniform => {
+                properties.monotonicity == Monotonicity::Constant
+            }
+            _ => true,
+        }
+    }
+
+    /// Picks an algorithm suited for the parameters; returns the algorithm and a
+    /// human-readable reason. The choice never violates the assumptions of an algorithm
+    /// (e.g. parallel and prefetching algorithms do not support resampling, and the parallel
+    /// algorithm does not support decreasing kernels), and it supports checkpoints if
+    /// `--checkpoint` or `--resume` is given; if `opt.num_threads` cannot be honored, it has to
+    /// be reset by the caller.
+    pub fn select_automatically(opt: &Parameters) -> (Self, &'static str) {
+        let weight_function = WeightFunction::new(opt.exponent, opt.offset);
+        let properties = weight_function.properties();
+        let num_threads = opt.num_threads.unwrap_or_else(num_cpus::get);
+        let checkpoints = opt.checkpoint.is_some() || opt.resume.is_some();
+
+        if weight_function.is_constant() {
+            return if num_threads > 1 && opt.nodes >= AUTO_MIN_NODES_PARALLEL && !checkpoints {
+                (
+                    SamplingAlgorithm::ParallelUniform,
+                    "constant weights and many nodes",
+                )
+            } else {
+                (SamplingAlgorithm::Uniform, "constant weights")
+            };
+        }
+
+        if opt.resample_previous {
+            return if properties.is_superlinear() {
+                (
+                    SamplingAlgorithm::DynWeightIndex,
+                    "resampling is only supported sequentially; superlinear weights",
+                )
+            } else {
+                (
+                    SamplingAlgorithm::PolyPA,
+                    "resampling is only supported by non-prefetching sequential algorithms",
+                )
+            };
+        }
+
+        if properties.is_superlinear() {
+            return (
+                SamplingAlgorithm::DynWeightIndex,
+                "superlinear weights yield few nodes of very high weight",
+            );
+        }
+
+        let parallel = num_threads > 1
+            && opt.nodes >= AUTO_MIN_NODES_PARALLEL
+            && SamplingAlgorithm::ParallelPolyPa.supports_kernel(properties);
+
+        if parallel && !checkpoints {
+            (
+                SamplingAlgorithm::ParallelPolyPa,
+                "multiple threads and many nodes",
+            )
+        } else if parallel {
+            let algorithm = if opt.nodes >= AUTO_MIN_NODES_PREFETCH {
+                SamplingAlgorithm::PolyPAPrefetch
+            } else {
+                SamplingAlgorithm::PolyPA
+            };
+            (
+                algorithm,
+                "checkpoints are only supported by sequential algorithms",
+            )
+        } else if opt.nodes >= AUTO_MIN_NODES_PREFETCH {
+            (
+                SamplingAlgorithm::PolyPAPrefetch,
+                "sequential; node infos exceed cache",
+            )
+        } else {
+            (SamplingAlgorithm::PolyPA, "sequential; few nodes")
+        }
+    }
+}
+
 pub fn get_and_check_options() -> Parameters {
-    let mut opt = Parameters::from_args();
+    check_options(Parameters::from_args())
+}
 
+pub fn check_options(mut opt: Parameters) -> Parameters {
     assert!(opt.initial_degree >= 1);
     if opt.seed_nodes.is_none() {
         opt.seed_nodes = Some(opt.initial_degree * 10);
@@ -73,10 +307,133 @@ pub fn get_and_check_options() -> Parameters {
     assert!(opt.seed_nodes.unwrap() >= opt.initial_degree);
     assert_eq!(opt.seed_nodes.unwrap() % 2, 0);
 
-    assert!(opt.exponent >= 0.0);
+    // the exponent may be negative, which yields anti-preferential kernels
     assert!(opt.offset >= 0.0);
 
     assert!(opt.num_threads.unwrap_or(1) > 0);
 
+    // bounds the number of nodes as well as the maximum degree
+    let max_degree = opt
+        .nodes
+        .checked_mul(opt.initial_degree)
+        .and_then(|x| x.checked_add(opt.seed_nodes.unwrap()));
+    assert!(
+        max_degree.is_some_and(|x| Node::try_from(x).is_ok()),
+        "Graph too large for {}-bit node ids",
+        8 * std::mem::size_of::<Node>()
+    );
+    assert!(
+        !opt.algorithm.uses_compact_node_info() || max_degree.unwrap() <= u32::MAX as usize,
+        "Graph too large for compact node infos"
+    );
+
+    if opt.algorithm == SamplingAlgorithm::Auto {
+        let (algorithm, reason) = SamplingAlgorithm::select_automatically(&opt);
+        println!("Auto-selected algorithm {:?}: {}", algorithm, reason);
+
+        if !algorithm.is_parallel() && opt.num_threads.is_some_and(|t| t > 1) {
+            println!("Ignore number of threads for sequential algorithm");
+            opt.num_threads = None;
+        }
+
+        opt.algorithm = algorithm;
+    }
+
+    // an explicitly chosen algorithm is kept, e.g. to compare it against the uniform null model
+    if WeightFunction::new(opt.exponent, opt.offset).is_constant() {
+        let algorithm = opt.algorithm.for_constant_weights();
+        if algorithm != opt.algorithm {
+            println!(
+                "Exponent is zero; {:?} would be faster than {:?}",
+                algorithm, opt.algorithm
+            );
+        }
+    }
+
+    if opt.pin_threads.is_some() && opt.algorithm != SamplingAlgorithm::ParallelPolyPa {
+        println!("Ignore thread pinning for {:?}", opt.algorithm);
+        opt.pin_threads = None;
+    }
+
+    if opt.prefetch_depth.is_some()
+        && !matches!(
+            opt.algorithm,
+            SamplingAlgorithm::PolyPAPrefetch | SamplingAlgorithm::PolyPAPrefetchCompact
+        )
+    {
+        println!("Ignore prefetch depth for {:?}", opt.algorithm);
+        opt.prefetch_depth = None;
+    }
+
+    if opt.batched_acceptance
+        && !matches!(
+            opt.algorithm,
+            SamplingAlgorithm::PolyPA
+                | SamplingAlgorithm::PolyPACompact
+                | SamplingAlgorithm::PolyPAExternalMemory
+                | SamplingAlgorithm::ParallelPolyPa
+        )
+    {
+        println!("Ignore batched acceptance for {:?}", opt.algorithm);
+        opt.batched_acceptance = false;
+    }
+
+    if opt.telemetry.is_some() && opt.algorithm != SamplingAlgorithm::ParallelPolyPa {
+        println!("Ignore telemetry for {:?}", opt.algorithm);
+        opt.telemetry = None;
+    }
+
+    if opt.seed_sequence.is_some() && !opt.algorithm.is_parallel() {
+        println!("Ignore seed sequence for {:?}", opt.algorithm);
+        opt.seed_sequence = None;
+    }
+
+    assert!(
+        opt.algorithm.supports_checkpoints() || (opt.checkpoint.is_none() && opt.resume.is_none()),
+        "Algorithm {:?} does not support checkpoints",
+        opt.algorithm
+    );
+    assert!(opt.checkpoint_interval > 0);
+    assert!(
+        opt.resume.is_none() || opt.prefetch_depth != Some(PrefetchDepth::Calibrate),
+        "Resuming requires the prefetch depth of the checkpoint rather than a calibrated one"
+    );
+
+    let properties = WeightFunction::new(opt.exponent, opt.offset).properties();
+    assert!(
+        opt.algorithm.supports_kernel(properties),
+        "Algorithm {:?} does not support kernels with {:?}",
+        opt.algorithm,
+        properties
+    );
+
     opt
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn auto_selection_supports_requested_checkpoints() {
+        let cases: [&[&str]; 5] = [
+            &["-e", "0.5", "-n", "2000000", "-t", "4"],
+            &["-e", "0.5", "-n", "20000000", "-t", "4"],
+            &["-e", "1.5", "-n", "2000000"],
+            &["-e", "1.5", "-n", "20000", "-l"],
+            &["-e", "0", "-n", "2000000", "-t", "4"],
+        ];
+
+        for args in cases {
+            for checkpoint in ["--checkpoint", "--resume"] {
+                let opt = check_options(Parameters::from_iter(
+                    ["rust-nlpa", "-a", "auto", "-d", "2", checkpoint, "x"]
+                        .iter()
+                        .chain(args),
+                ));
+
+                assert!(opt.algorithm.supports_checkpoints(), "{:?}", opt.algorithm);
+            }
+        }
+    }
+}
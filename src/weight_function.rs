@@ -1,5 +1,6 @@
 use super::Node;
 use std::cmp::Ordering;
+use std::ops::Range;
 
 const NUM_PRECOMPUTED: usize = 100;
 
@@ -11,13 +12,41 @@ pub struct WeightFunction {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub enum Regime {
-    Sublinear,
+pub enum Monotonicity {
+    Constant,
+    Increasing,
+    Decreasing,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Curvature {
+    Concave,
     Linear,
-    Superlinear,
+    Convex,
+}
+
+/// Shape of a kernel on the positive degrees. The parallel algorithms derive their bounds on the
+/// weight increase within an epoch from these properties.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct KernelProperties {
+    pub monotonicity: Monotonicity,
+    pub curvature: Curvature,
+}
+
+impl KernelProperties {
+    /// Whether the weight of a node never decreases as it gains edges
+    pub fn is_non_decreasing(&self) -> bool {
+        self.monotonicity != Monotonicity::Decreasing
+    }
+
+    /// Whether few nodes of very high weight emerge (i.e. superlinear preferential attachment)
+    pub fn is_superlinear(&self) -> bool {
+        self.monotonicity == Monotonicity::Increasing && self.curvature == Curvature::Convex
+    }
 }
 
 /// Implements the function `f(d) = d**exponent + offset` with pre-computation of the first few values.
+/// A negative exponent yields an anti-preferential kernel, which is infinite for degree zero.
 ///
 /// # Example
 /// ```
@@ -34,7 +63,7 @@ impl WeightFunction {
         let mut precomputed = [0.0; NUM_PRECOMPUTED];
 
         for (degree, weight) in precomputed.iter_mut().enumerate() {
-            *weight = Self::compute(exponent, offset, degree);
+            *weight = Self::compute(exponent, offset, degree as Node);
         }
 
         Self {
@@ -45,7 +74,7 @@ impl WeightFunction {
     }
 
     pub fn get(&self, degree: Node) -> f64 {
-        if NUM_PRECOMPUTED > degree {
+        if NUM_PRECOMPUTED > degree as usize {
             unsafe { *self.precomputed.get_unchecked(degree as usize) }
         } else {
             Self::compute(self.exponent, self.offset, degree)
@@ -60,11 +89,43 @@ impl WeightFunction {
         self.exponent
     }
 
-    pub fn regime(&self) -> Regime {
-        match self.exponent.partial_cmp(&1.0).unwrap() {
-            Ordering::Less => Regime::Sublinear,
-            Ordering::Equal => Regime::Linear,
-            Ordering::Greater => Regime::Superlinear,
+    /// Returns true iff all degrees are assigned the same weight (i.e. uniform attachment)
+    pub fn is_constant(&self) -> bool {
+        self.exponent == 0.0
+    }
+
+    pub fn properties(&self) -> KernelProperties {
+        let monotonicity = match self.exponent.partial_cmp(&0.0).unwrap() {
+            Ordering::Less => Monotonicity::Decreasing,
+            Ordering::Equal => Monotonicity::Constant,
+            Ordering::Greater => Monotonicity::Increasing,
+        };
+
+        // d**e is convex for e < 0 and e > 1, and concave for 0 < e < 1
+        let curvature = if self.exponent == 0.0 || self.exponent == 1.0 {
+            Curvature::Linear
+        } else if 0.0 < self.exponent && self.exponent < 1.0 {
+            Curvature::Concave
+        } else {
+            Curvature::Convex
+        };
+
+        KernelProperties {
+            monotonicity,
+            curvature,
+        }
+    }
+
+    /// Returns the largest increase `f(d + 1) - f(d)` for `d` in the non-empty range `degrees`
+    /// (i.e. a Lipschitz bound on the range). Since the increases are monotone in `d` by
+    /// curvature, the maximum is attained at one end of the range.
+    pub fn max_increment(&self, degrees: Range<Node>) -> f64 {
+        debug_assert!(!degrees.is_empty());
+        let increment = |d: Node| self.get(d + 1) - self.get(d);
+
+        match self.properties().curvature {
+            Curvature::Concave | Curvature::Linear => increment(degrees.start),
+            Curvature::Convex => increment(degrees.end - 1),
         }
     }
 
@@ -79,7 +140,7 @@ mod test {
     use super::*;
 
     fn validate(wf: WeightFunction, reference: impl Fn(Node) -> f64) {
-        for d in 0..2 * NUM_PRECOMPUTED {
+        for d in 0..2 * NUM_PRECOMPUTED as Node {
             let w = wf.get(d);
             let r = reference(d);
 
@@ -104,6 +165,13 @@ mod test {
         validate(WeightFunction::new(0.0, 1.0), |_| 2.0);
     }
 
+    #[test]
+    fn constant() {
+        assert!(WeightFunction::new(0.0, 0.0).is_constant());
+        assert!(WeightFunction::new(0.0, 3.0).is_constant());
+        assert!(!WeightFunction::new(0.5, 0.0).is_constant());
+    }
+
     #[test]
     fn cross_sqrt() {
         validate(WeightFunction::new(0.5, 0.0), |d| (d as f64).sqrt());
@@ -121,4 +189,31 @@ mod test {
         validate(WeightFunction::new(2.0, 0.0), |d| (d * d) as f64);
         validate(WeightFunction::new(2.0, 4.0), |d| (d * d) as f64 + 4.0);
     }
+
+    #[test]
+    fn properties() {
+        let properties = |e| WeightFunction::new(e, 1.0).properties();
+
+        assert_eq!(properties(0.0).monotonicity, Monotonicity::Constant);
+        assert_eq!(properties(0.5).curvature, Curvature::Concave);
+        assert_eq!(properties(1.0).curvature, Curvature::Linear);
+        assert!(properties(1.5).is_superlinear());
+
+        assert_eq!(properties(-0.5).monotonicity, Monotonicity::Decreasing);
+        assert!(!properties(-0.5).is_non_decreasing());
+        assert!(!properties(-0.5).is_superlinear());
+    }
+
+    #[test]
+    fn max_increment_bounds_increments() {
+        for exponent in [-1.0, -0.5, 0.5, 1.0, 1.5, 2.0] {
+            let wf = WeightFunction::new(exponent, 1.0);
+            for degrees in [1..2, 1..50, 3..200, 150..160] {
+                let bound = wf.max_increment(degrees.clone());
+                assert!(degrees
+                    .map(|d| wf.get(d + 1) - wf.get(d))
+                    .all(|inc| inc <= bound * (1.0 + 1e-12) + 1e-12));
+            }
+        }
+    }
 }